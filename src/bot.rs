@@ -1,12 +1,22 @@
 use crate::{
-    history::MessageHistory,
+    config::{ChannelConfig, IrcConfig, RateLimit, UberConfig},
+    database::ExecutorConnection,
+    error::{friendly_message, ErrorReporter, UserError},
+    formatting::strip_formatting,
+    history::{HistoryRegistry, MessageHistory},
+    optout::OptOutList,
+    ratelimit::RateLimiter,
     regex_util::{FancyRegexExt, OwnedCaptures},
-    ExecutorConnection,
+    transport::split_target,
 };
 use async_trait::async_trait;
 use fancy_regex::Regex;
-use std::{collections::HashMap, sync::Arc};
-use tokio::sync::mpsc;
+use futures_util::FutureExt;
+use std::{
+    any::Any, collections::HashMap, future::Future, panic::AssertUnwindSafe, sync::Arc,
+    time::Duration,
+};
+use tokio::sync::{mpsc, watch};
 
 #[async_trait]
 pub trait Trigger {
@@ -21,31 +31,184 @@ pub trait Command {
 pub struct CommandContext {
     pub history: Arc<MessageHistory>,
     pub author: String,
+    /// Channel the command was sent to
+    pub channel: String,
+    /// Whether the author is one of the bot's admins
+    pub is_admin: bool,
     pub content: Option<String>,
     pub db: ExecutorConnection,
+    /// Language of the channel, if configured
+    pub language: Option<String>,
+    /// Name of the network the command was sent on
+    pub network: String,
+    /// Network-qualified channels sharing their quotes with this channel,
+    /// starting with the channel itself
+    pub quote_channels: Vec<String>,
+    /// Users who don't want their messages to be stored or quoted
+    pub opt_outs: Arc<OptOutList>,
+    /// Message histories of all networks, including [`CommandContext::history`]
+    pub histories: Arc<HistoryRegistry>,
 }
 
 pub struct TriggerContext {
     pub history: Arc<MessageHistory>,
     pub author: String,
+    /// Channel the message was sent to
+    pub channel: String,
     // we can omit content because it's the same as captures.get(0).unwrap()
     pub captures: OwnedCaptures,
     pub db: ExecutorConnection,
+    /// Language of the channel, if configured
+    pub language: Option<String>,
 }
 
-pub struct TriggerEntry {
+pub(crate) type CommandHandler = Arc<dyn Command + Send + Sync>;
+pub(crate) type TriggerHandler = Arc<dyn Trigger + Send + Sync>;
+
+struct TriggerEntry {
     name: String,
     regex: Regex,
-    handler: Arc<dyn Trigger + Send + Sync>,
+    handler: TriggerHandler,
+}
+
+/// Bot settings that can be changed while the bot is running
+#[derive(Clone, Default)]
+pub struct BotSettings {
+    pub prefixes: Vec<String>,
+    /// Trigger name -> channels where it's disabled (`*` disables it everywhere)
+    pub ignored_triggers: HashMap<String, Vec<String>>,
+    pub admin_channel: Option<String>,
+    pub admins: Vec<String>,
+    /// Lowercase channel name -> settings of that channel
+    pub channels: HashMap<String, ChannelConfig>,
+    /// Name of the network
+    pub network: String,
+    /// Quote namespace -> network-qualified channels of all networks that share it
+    pub quote_namespaces: HashMap<String, Vec<String>>,
+}
+
+impl BotSettings {
+    /// Creates the settings for a network from the config
+    pub fn new(cfg: &UberConfig, name: &str, network: &IrcConfig) -> Self {
+        let mut quote_namespaces: HashMap<String, Vec<String>> = HashMap::new();
+        for (network, _) in cfg.networks() {
+            for (channel, c) in cfg.channel_configs(network) {
+                if let Some(namespace) = c.quote_namespace {
+                    quote_namespaces
+                        .entry(namespace)
+                        .or_default()
+                        .push(format!("{network}/{channel}"));
+                }
+            }
+        }
+        Self {
+            prefixes: cfg.prefixes(network),
+            ignored_triggers: cfg.bot.ignored_triggers.clone().unwrap_or_default(),
+            admin_channel: cfg.bot.admin_channel.clone(),
+            admins: cfg.bot.admins.clone(),
+            channels: cfg.channel_configs(name),
+            network: name.to_string(),
+            quote_namespaces,
+        }
+    }
+
+    fn channel(&self, channel: &str) -> Option<&ChannelConfig> {
+        self.channels.get(&channel.to_ascii_lowercase())
+    }
+
+    pub fn is_admin(&self, nick: &str) -> bool {
+        self.admins.iter().any(|a| a.eq_ignore_ascii_case(nick))
+    }
+
+    /// Returns the command prefixes used in a channel
+    pub fn prefixes(&self, channel: &str) -> &[String] {
+        self.channel(channel)
+            .and_then(|c| c.prefixes.as_deref())
+            .unwrap_or(&self.prefixes)
+    }
+
+    pub fn is_command_enabled(&self, command: &str, channel: &str) -> bool {
+        let Some(c) = self.channel(channel) else {
+            return true;
+        };
+        is_enabled(command, c.commands.as_deref(), &c.disabled_commands)
+    }
+
+    pub fn is_trigger_enabled(&self, trigger: &str, channel: &str) -> bool {
+        if let Some(ign) = self.ignored_triggers.get(trigger) {
+            if ign.iter().any(|c| c == "*" || c == channel) {
+                return false;
+            }
+        }
+        let Some(c) = self.channel(channel) else {
+            return true;
+        };
+        is_enabled(trigger, c.triggers.as_deref(), &c.disabled_triggers)
+    }
+
+    /// Returns whether replies in a channel can contain IRC formatting
+    pub fn color(&self, channel: &str) -> bool {
+        self.channel(channel).and_then(|c| c.color).unwrap_or(true)
+    }
+
+    pub fn rate_limit(&self, channel: &str) -> Option<&RateLimit> {
+        self.channel(channel).and_then(|c| c.rate_limit.as_ref())
+    }
+
+    pub fn language(&self, channel: &str) -> Option<&str> {
+        self.channel(channel).and_then(|c| c.language.as_deref())
+    }
+
+    /// Returns the network-qualified name of a channel on this network, which quotes are stored with
+    pub fn quote_channel(&self, channel: &str) -> String {
+        format!("{}/{}", self.network, channel.to_ascii_lowercase())
+    }
+
+    /// Returns the network-qualified channels in the same quote namespace as `channel`,
+    /// starting with the channel itself
+    pub fn quote_channels(&self, channel: &str) -> Vec<String> {
+        let own = self.quote_channel(channel);
+        let mut channels = vec![own.clone()];
+        if let Some(namespace) = self
+            .channel(channel)
+            .and_then(|c| c.quote_namespace.as_ref())
+        {
+            let shared = self.quote_namespaces.get(namespace).into_iter().flatten();
+            channels.extend(shared.filter(|c| **c != own).cloned());
+        }
+        // quotes grabbed before channels were qualified only have the channel name
+        let unqualified: Vec<String> = channels
+            .iter()
+            .map(|c| split_target(c).1.to_string())
+            .collect();
+        for channel in unqualified {
+            if !channels.contains(&channel) {
+                channels.push(channel);
+            }
+        }
+        channels
+    }
+}
+
+fn is_enabled(name: &str, enabled: Option<&[String]>, disabled: &[String]) -> bool {
+    if let Some(enabled) = enabled {
+        if !enabled.iter().any(|n| n == name) {
+            return false;
+        }
+    }
+    !disabled.iter().any(|n| n == name)
 }
 
 pub struct Bot<SF: Fn(String, String) -> anyhow::Result<()>> {
     history: Arc<MessageHistory>,
-    prefixes: Vec<String>,
+    histories: Arc<HistoryRegistry>,
+    opt_outs: Arc<OptOutList>,
+    settings: watch::Receiver<Arc<BotSettings>>,
     db: ExecutorConnection,
-    commands: HashMap<String, Arc<dyn Command + Send + Sync>>,
+    commands: HashMap<String, CommandHandler>,
     triggers: Vec<TriggerEntry>,
-    ignored_triggers: Option<HashMap<String, Vec<String>>>,
+    reporter: Arc<ErrorReporter>,
+    rate_limiter: RateLimiter,
     sendmsg: Arc<SF>,
 }
 
@@ -63,30 +226,49 @@ fn dissect<'a>(prefixes: &[String], str: &'a str) -> Option<(&'a str, Option<&'a
     None
 }
 
+/// Extracts a printable message from a panic payload
+fn panic_message(payload: &(dyn Any + Send)) -> &str {
+    payload
+        .downcast_ref::<&str>()
+        .copied()
+        .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+        .unwrap_or("unknown panic payload")
+}
+
 impl<SF> Bot<SF>
 where
     SF: Fn(String, String) -> anyhow::Result<()> + Send + Sync + 'static,
 {
+    /// Creates a new bot, `settings` can be used to update its settings while it's running.
+    /// `opt_outs` has to contain the users who opted out, it's shared by the bots of all networks
+    /// like `histories`, which the history of the new bot is added to.
     pub fn new(
-        prefixes: Vec<String>,
+        settings: watch::Receiver<Arc<BotSettings>>,
         db: ExecutorConnection,
+        opt_outs: Arc<OptOutList>,
+        histories: Arc<HistoryRegistry>,
         hdepth: usize,
         sendmsg: SF,
-        ignored_triggers: Option<HashMap<String, Vec<String>>>,
+        report_cooldown: Duration,
     ) -> Self {
+        let history = Arc::new(MessageHistory::new(hdepth));
+        histories.register(history.clone());
         Bot {
-            history: Arc::new(MessageHistory::new(hdepth)),
+            history,
+            histories,
+            opt_outs,
             commands: HashMap::new(),
             triggers: Vec::new(),
-            prefixes,
+            settings,
             db,
             sendmsg: Arc::new(sendmsg),
-            ignored_triggers,
+            reporter: Arc::new(ErrorReporter::new(report_cooldown)),
+            rate_limiter: RateLimiter::default(),
         }
     }
 
     pub fn add_command<C: Command + Send + Sync + 'static>(&mut self, name: String, cmd: C) {
-        self.commands.insert(name, Arc::new(cmd));
+        self.insert_command(name, Arc::new(cmd));
     }
 
     pub fn add_trigger<C: Trigger + Send + Sync + 'static>(
@@ -95,23 +277,22 @@ where
         regex: Regex,
         trig: C,
     ) {
-        if let Some(ign) = self.ignored_triggers.as_ref().and_then(|v| v.get(&name)) {
-            if ign.iter().any(|v| *v == name) {
-                tracing::debug!(
-                    "Not installing trigger {}, because it's globally ignored",
-                    name
-                );
-                return;
-            }
-        }
+        self.insert_trigger(name, regex, Arc::new(trig));
+    }
+
+    pub(crate) fn insert_command(&mut self, name: String, cmd: CommandHandler) {
+        self.commands.insert(name, cmd);
+    }
+
+    pub(crate) fn insert_trigger(&mut self, name: String, regex: Regex, trig: TriggerHandler) {
         self.triggers.push(TriggerEntry {
             name,
             regex,
-            handler: Arc::new(trig),
+            handler: trig,
         });
     }
 
-    pub(crate) async fn handle_message(
+    pub async fn handle_message(
         &self,
         origin: String,
         author: String,
@@ -119,32 +300,58 @@ where
         cancel: mpsc::Sender<()>,
     ) {
         let content = content.trim();
+        // the settings are shared, holding on to them doesn't block reloads or copy them
+        let settings = Arc::clone(&self.settings.borrow());
+        let language = settings.language(&origin).map(ToString::to_string);
+        // messages of users who opted out are neither stored nor logged
+        let opted_out = self.opt_outs.contains(&author);
+        let logged = |text: &str| {
+            if opted_out {
+                "[redacted]".to_string()
+            } else {
+                text.to_string()
+            }
+        };
         // first we check if the message is a command
-        if let Some((command, remainder)) = dissect(&self.prefixes, content) {
-            tracing::debug!("Got command: {:?} -> {:?}", command, remainder);
+        let command = dissect(settings.prefixes(&origin), content);
+        if let Some((command, remainder)) = command {
+            tracing::debug!("Got command: {:?} -> {:?}", command, remainder.map(logged));
             if command.is_empty() {
                 return;
             }
             // now we need to find a handler for this command
-            if let Some(handler) = self.commands.get(command) {
+            if let Some(handler) = self
+                .commands
+                .get(command)
+                .filter(|_| settings.is_command_enabled(command, &origin))
+            {
+                if !self.check_rate_limit(&settings, &origin) {
+                    return;
+                }
                 // we found a command, we can now spawn its handler
                 let ctx = CommandContext {
-                    author,
+                    author: author.clone(),
+                    channel: origin.clone(),
+                    is_admin: settings.is_admin(&author),
                     content: remainder.map(ToString::to_string),
                     db: self.db.clone(),
                     history: self.history.clone(),
+                    language,
+                    network: settings.network.clone(),
+                    quote_channels: settings.quote_channels(&origin),
+                    opt_outs: self.opt_outs.clone(),
+                    histories: self.histories.clone(),
                 };
-                let sendmsg = self.sendmsg.clone();
                 let handler = handler.clone();
-                tokio::spawn(async move {
-                    #[allow(clippy::no_effect_underscore_binding)]
-                    let _cancel = cancel;
-                    let result = handler
-                        .execute(ctx)
-                        .await
-                        .unwrap_or_else(|e| format!("Error: {e}"));
-                    (sendmsg)(origin, result)
-                });
+                self.spawn_handler(
+                    &settings,
+                    command.into(),
+                    logged(remainder.unwrap_or_default()),
+                    origin,
+                    author,
+                    cancel,
+                    async move { handler.execute(ctx).await },
+                );
                 return;
             }
             // no handler found :c
@@ -158,44 +365,131 @@ where
             let captures = trigger.regex.owned_captures(&content).unwrap();
             // we need to find a regex that matches this message
             if let Some(captures) = captures {
-                // check if it's not ignored
-                if let Some(ign) = self
-                    .ignored_triggers
-                    .as_ref()
-                    .and_then(|v| v.get(&trigger.name))
-                {
-                    if ign.contains(&origin) {
-                        tracing::debug!(
-                            "Skipping ignored trigger {} for channel {}",
-                            trigger.name,
-                            origin
-                        );
-                        break;
-                    }
+                // check if it's enabled in this channel
+                if !settings.is_trigger_enabled(&trigger.name, &origin) {
+                    tracing::debug!(
+                        "Skipping disabled trigger {} for channel {}",
+                        trigger.name,
+                        origin
+                    );
+                    break;
+                }
+                if !self.check_rate_limit(&settings, &origin) {
+                    return;
                 }
                 // and spawn the trigger handler
                 let ctx = TriggerContext {
-                    author,
+                    author: author.clone(),
+                    channel: origin.clone(),
                     captures,
                     db: self.db.clone(),
                     history: self.history.clone(),
+                    language,
                 };
-                let sendmsg = self.sendmsg.clone();
                 let handler = trigger.handler.clone();
-                tokio::spawn(async move {
-                    #[allow(clippy::no_effect_underscore_binding)]
-                    let _cancel = cancel;
-                    let result = handler
-                        .execute(ctx)
-                        .await
-                        .unwrap_or_else(|e| format!("Error: {e}"));
-                    (sendmsg)(origin, result)
-                });
+                self.spawn_handler(
+                    &settings,
+                    trigger.name.clone(),
+                    logged(&content),
+                    origin,
+                    author,
+                    cancel,
+                    async move { handler.execute(ctx).await },
+                );
                 return;
             }
         }
         // no regex matched the message, so it's neither a command nor a trigger
         // it's a regular message, so we add it to the message history
-        self.history.add_message(&author, content).await;
+        if !opted_out {
+            self.history.add_message(&origin, &author, content).await;
+        }
+    }
+
+    /// Returns `false` if the bot shouldn't reply in `channel` because of its rate limit
+    fn check_rate_limit(&self, settings: &BotSettings, channel: &str) -> bool {
+        let Some(limit) = settings.rate_limit(channel) else {
+            return true;
+        };
+        let allowed = self.rate_limiter.try_acquire(channel, limit);
+        if !allowed {
+            tracing::debug!("Rate limit reached in {}, ignoring message", channel);
+        }
+        allowed
+    }
+
+    /// Runs a command or trigger handler in a separate task and sends its result to `origin`.
+    /// Errors and panics are logged, replaced with a user-friendly message
+    /// and reported to the admin channel (if set).
+    #[allow(clippy::too_many_arguments)]
+    fn spawn_handler<F>(
+        &self,
+        settings: &BotSettings,
+        name: String,
+        input: String,
+        origin: String,
+        author: String,
+        cancel: mpsc::Sender<()>,
+        handler: F,
+    ) where
+        F: Future<Output = anyhow::Result<String>> + Send + 'static,
+    {
+        let sendmsg = self.sendmsg.clone();
+        let reporter = self.reporter.clone();
+        let admin_channel = settings.admin_channel.clone();
+        let color = settings.color(&origin);
+        tokio::spawn(async move {
+            #[allow(clippy::no_effect_underscore_binding)]
+            let _cancel = cancel;
+            let (reply, report) = match AssertUnwindSafe(handler).catch_unwind().await {
+                Ok(Ok(reply)) => (reply, None),
+                Ok(Err(e)) if e.is::<UserError>() => (e.to_string(), None),
+                Ok(Err(e)) => {
+                    tracing::error!(
+                        "Handler '{}' failed in {} (author: {}, input: {:?}): {:?}",
+                        name,
+                        origin,
+                        author,
+                        input,
+                        e
+                    );
+                    let report = format!(
+                        "Handler '{name}' failed in {origin} (author: {author}, input: {input:?}): {e:#}"
+                    );
+                    (friendly_message(&e), Some((format!("{name}: {e}"), report)))
+                }
+                Err(payload) => {
+                    let panic_msg = panic_message(payload.as_ref());
+                    tracing::error!(
+                        "Handler '{}' panicked in {} (author: {}, input: {:?}): {}",
+                        name,
+                        origin,
+                        author,
+                        input,
+                        panic_msg
+                    );
+                    let report = format!(
+                        "Handler '{name}' panicked in {origin} (author: {author}, input: {input:?}): {panic_msg}"
+                    );
+                    (
+                        "An internal error has occurred.".into(),
+                        Some((format!("{name}: {panic_msg}"), report)),
+                    )
+                }
+            };
+            if let (Some((key, report)), Some(target)) = (report, admin_channel) {
+                if let Some(report) = reporter.prepare(key, &report) {
+                    if let Err(e) = (sendmsg)(target, report) {
+                        tracing::error!("Failed to send an error report: {}", e);
+                    }
+                }
+            }
+            let reply = if color {
+                reply
+            } else {
+                strip_formatting(&reply)
+            };
+            (sendmsg)(origin, reply)
+        });
     }
 }
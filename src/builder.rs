@@ -0,0 +1,425 @@
+use crate::{
+    bot::{Bot, BotSettings, Command, CommandHandler, Trigger, TriggerHandler},
+    commands::{
+        help::Help,
+        leek::{Leet, Mock, Owo},
+        privacy::{ForgetMe, OptIn, OptOut},
+        qotd::{self, Qotd},
+        quotes::{
+            Convo, Grab, Quot, QuoteById, Search, SearchPage, Stats, Top, Ungrab, Unquote, Vote,
+        },
+        sed::Sed,
+    },
+    config::UberConfig,
+    database::{DbExecutor, DbMaintenance},
+    history::HistoryRegistry,
+    optout::OptOutList,
+    reload::{ReloadSignal, Reloader},
+    transport::{self, console, irc, MessageStream, Router, Transport},
+};
+use anyhow::Context;
+use fancy_regex::Regex;
+use futures_util::future;
+use std::{
+    collections::HashMap,
+    future::Future,
+    path::{Path, PathBuf},
+    sync::Arc,
+    thread,
+    time::Duration,
+};
+use tokio::{
+    select,
+    sync::{broadcast, watch},
+};
+
+const SED_REGEX: &str = r"^(?:(?<u>\S+):\s+)?s/(?<r>[^/]*)/(?<w>[^/]*)(?:/(?<f>[a-z]*))?\s*";
+#[cfg(feature = "spotify")]
+const SPOTIFY_REGEX: &str =
+    r"(?:https?|spotify):(?://open\.spotify\.com/)?(track|artist|album|playlist)[/:]([a-zA-Z\d]*)";
+#[cfg(feature = "title")]
+const TITLE_REGEX: &str =
+    r"https?://[-a-zA-Z0-9@:%._+~#=]{1,}\.[a-zA-Z0-9()]{1,}\b[-a-zA-Z0-9()@:%_+.~#?&/=]*";
+
+/// Names and patterns of the built-in triggers
+const BUILTIN_TRIGGERS: &[(&str, &str)] = &[
+    ("sed", SED_REGEX),
+    #[cfg(feature = "spotify")]
+    ("spotify", SPOTIFY_REGEX),
+    #[cfg(feature = "title")]
+    ("title", TITLE_REGEX),
+];
+
+#[cfg(unix)]
+async fn terminate_signal() {
+    use tokio::signal::unix::{signal, SignalKind};
+    let mut sigterm = signal(SignalKind::terminate()).unwrap();
+    let mut sigint = signal(SignalKind::interrupt()).unwrap();
+    tracing::debug!("Installed ctrl+c handler");
+    select! {
+        _ = sigterm.recv() => (),
+        _ = sigint.recv() => ()
+    }
+}
+
+#[cfg(windows)]
+async fn terminate_signal() {
+    use tokio::signal::windows::ctrl_c;
+    let mut ctrlc = ctrl_c().unwrap();
+    tracing::debug!("Installed ctrl+c handler");
+    let _ = ctrlc.recv().await;
+}
+
+/// Wires up the database, IRC connection, web service and the bot itself.
+///
+/// Commands and triggers registered here are installed into the bot when it's started
+/// with [`BotBuilder::run`], which keeps running until a shutdown signal is received.
+pub struct BotBuilder {
+    cfg: UberConfig,
+    config_path: Option<PathBuf>,
+    commands: Vec<(String, CommandHandler)>,
+    triggers: Vec<(String, Regex, TriggerHandler)>,
+}
+
+impl BotBuilder {
+    pub fn new(cfg: UberConfig) -> Self {
+        Self {
+            cfg,
+            config_path: None,
+            commands: Vec::new(),
+            triggers: Vec::new(),
+        }
+    }
+
+    pub fn config(&self) -> &UberConfig {
+        &self.cfg
+    }
+
+    /// Enables reloading the config from `path` when SIGHUP is received
+    pub fn config_path(&mut self, path: impl Into<PathBuf>) -> &mut Self {
+        self.config_path = Some(path.into());
+        self
+    }
+
+    pub fn add_command<C: Command + Send + Sync + 'static>(
+        &mut self,
+        name: impl Into<String>,
+        cmd: C,
+    ) -> &mut Self {
+        self.commands.push((name.into(), Arc::new(cmd)));
+        self
+    }
+
+    pub fn add_trigger<T: Trigger + Send + Sync + 'static>(
+        &mut self,
+        name: impl Into<String>,
+        regex: Regex,
+        trig: T,
+    ) -> &mut Self {
+        self.triggers.push((name.into(), regex, Arc::new(trig)));
+        self
+    }
+
+    /// Checks the setup without connecting anywhere: validates the config,
+    /// compiles the built-in trigger regexes and opens the database.
+    /// Returns warnings about problems that don't prevent the bot from starting.
+    pub fn check(&self) -> anyhow::Result<Vec<String>> {
+        self.cfg.validate()?;
+        for (name, pattern) in BUILTIN_TRIGGERS {
+            Regex::new(pattern).with_context(|| format!("Invalid regex of trigger '{name}'"))?;
+        }
+        let mut warnings = Vec::new();
+        let triggers: Vec<&str> = BUILTIN_TRIGGERS
+            .iter()
+            .map(|(name, _)| *name)
+            .chain(self.triggers.iter().map(|(name, _, _)| name.as_str()))
+            .collect();
+        for name in self.cfg.bot.ignored_triggers.iter().flat_map(HashMap::keys) {
+            if !triggers.contains(&name.as_str()) {
+                warnings.push(format!("bot.ignored_triggers: unknown trigger '{name}'"));
+            }
+        }
+        let db_path = self.cfg.db_path();
+        if Path::new(db_path).exists() {
+            let db = DbMaintenance::open(db_path)
+                .with_context(|| format!("Couldn't open the database '{db_path}'"))?;
+            for problem in db.check()? {
+                warnings.push(format!("Database integrity: {problem}"));
+            }
+            let pending = db.pending_migrations()?;
+            if pending > 0 {
+                warnings.push(format!(
+                    "The database will be migrated ({pending} pending migrations)"
+                ));
+            }
+        } else {
+            warnings.push(format!(
+                "The database '{db_path}' doesn't exist, it will be created"
+            ));
+        }
+        Ok(warnings)
+    }
+
+    /// Registers all commands and triggers that are shipped with überbot
+    #[cfg_attr(not(feature = "spotify"), allow(clippy::unused_async))]
+    pub async fn add_builtin_commands(&mut self) -> anyhow::Result<&mut Self> {
+        let bot = &self.cfg.bot;
+        let quot = Quot::new(bot.weighted_quotes);
+        let ungrab = Ungrab::new(bot.ungrab_window.unwrap_or(300));
+        self.add_command("help", Help)
+            .add_command("owo", Owo)
+            .add_command("leet", Leet)
+            .add_command("mock", Mock)
+            .add_command("grab", Grab)
+            .add_command("convo", Convo)
+            .add_command("ungrab", ungrab)
+            .add_command("quot", quot)
+            .add_command("quote", QuoteById)
+            .add_command("unquote", Unquote)
+            .add_command("+1", Vote::up())
+            .add_command("-1", Vote::down())
+            .add_command("optout", OptOut)
+            .add_command("optin", OptIn)
+            .add_command("forgetme", ForgetMe::default());
+        let search_limit = self.cfg.bot.search_limit.unwrap_or(3);
+        let qotd = Qotd::new(self.cfg.qotd.as_ref())?;
+        self.add_command("qtop", Top::new(search_limit))
+            .add_command("qstats", Stats)
+            .add_command("qsearch", Search::new(search_limit))
+            .add_command("qnext", SearchPage::next(search_limit))
+            .add_command("qprev", SearchPage::previous(search_limit))
+            .add_command("qpage", SearchPage::jump(search_limit))
+            .add_command("qotd", qotd);
+        self.add_trigger("sed", Regex::new(SED_REGEX)?, Sed);
+        #[cfg(feature = "waifu")]
+        self.add_command("waifu", crate::commands::waifu::Waifu::default());
+        #[cfg(feature = "eval")]
+        self.add_command("ev", crate::commands::eval::Eval::default());
+        #[cfg(feature = "spotify")]
+        if let Some(spotcfg) = &self.cfg.spotify {
+            use crate::commands::spotify::Spotify;
+            use rspotify::Credentials;
+            let secret = spotcfg
+                .client_secret
+                .as_ref()
+                .context("The Spotify client secret is missing")?;
+            let creds = Credentials::new(&spotcfg.client_id, secret.expose());
+            let spotify = Spotify::new(creds).await?;
+            self.add_trigger("spotify", Regex::new(SPOTIFY_REGEX)?, spotify);
+        } else {
+            tracing::warn!("Spotify module is disabled, because the config is missing");
+        }
+        #[cfg(feature = "title")]
+        {
+            let title = crate::commands::title::Title::new(self.cfg.bot.user_agent.clone())?;
+            self.add_trigger("title", Regex::new(TITLE_REGEX)?, title);
+        }
+        #[cfg(feature = "debug")]
+        {
+            use crate::commands::debug::{LastMsg, Sleep};
+            self.add_command("lastmsg", LastMsg)
+                .add_command("sleep", Sleep);
+        }
+        Ok(self)
+    }
+
+    /// Connects to IRC and runs the bot until a shutdown signal is received or a fatal error occurs
+    pub async fn run(self) -> anyhow::Result<()> {
+        self.run_until(terminate_signal()).await
+    }
+
+    /// Connects to IRC and runs the bot until `shutdown` completes or a fatal error occurs
+    pub async fn run_until<F: Future<Output = ()>>(self, shutdown: F) -> anyhow::Result<()> {
+        self.cfg.validate()?;
+        let mut connections = Vec::new();
+        for (name, network) in self.cfg.networks() {
+            tracing::info!("Connecting to network '{}' ({})", name, network.host);
+            let (transport, messages) = irc::connect(network, &self.cfg.channel_keys(name)).await?;
+            connections.push(Connection {
+                network: name.to_string(),
+                transport: Arc::new(transport),
+                messages,
+            });
+        }
+        self.start(connections, shutdown).await
+    }
+
+    /// Runs the bot without connecting to IRC, reading messages from stdin
+    /// as if they were sent by `nick` in `channel`. The console acts as the first configured network.
+    pub async fn run_console(self, nick: String, channel: String) -> anyhow::Result<()> {
+        self.cfg.validate()?;
+        let (name, network) = self.cfg.networks()[0];
+        let bot_nick = network.nickname.as_ref().unwrap_or(&network.username);
+        let (transport, messages) = console::start(bot_nick.clone(), nick, channel);
+        let connection = Connection {
+            network: name.to_string(),
+            transport: Arc::new(transport),
+            messages,
+        };
+        self.start(vec![connection], terminate_signal()).await
+    }
+
+    async fn start<F: Future<Output = ()>>(
+        self,
+        connections: Vec<Connection>,
+        shutdown: F,
+    ) -> anyhow::Result<()> {
+        let cfg = self.cfg;
+        let mut reload_signal = ReloadSignal::new()?;
+        let (db_exec, db_conn) = DbExecutor::create(cfg.db_path())?;
+        let exec_thread = thread::spawn(move || db_exec.run());
+        let opt_outs = Arc::new(OptOutList::new(db_conn.opted_out().await?));
+        let histories = Arc::new(HistoryRegistry::default());
+
+        let router = Arc::new(Router::new(
+            connections
+                .iter()
+                .map(|c| (c.network.clone(), c.transport.clone()))
+                .collect(),
+        ));
+        let (ctx, _) = broadcast::channel(1);
+
+        #[cfg(feature = "web")]
+        let (http_task, webhooks_tx) = cfg.web.as_ref().map_or((None, None), |http| {
+            use crate::web::{self, HttpContext};
+            let http_ctx = ctx.subscribe();
+            let router = router.clone();
+            let (tx, webhooks) = watch::channel(http.webhooks.clone());
+            let context = HttpContext {
+                listen: http.listen,
+                webhooks,
+                sendmsg: move |target: String, msg| router.send(None, &target, msg),
+            };
+            let task = tokio::spawn(async move {
+                if let Err(e) = web::run(context, http_ctx).await {
+                    tracing::error!("Fatal error in web service: {}", e);
+                }
+            });
+            (Some(task), Some(tx))
+        });
+        #[cfg(not(feature = "web"))]
+        let webhooks_tx = None;
+
+        let mut loops = Vec::with_capacity(connections.len());
+        let mut settings = HashMap::new();
+        for connection in connections {
+            let network = cfg
+                .networks()
+                .into_iter()
+                .find(|(n, _)| *n == connection.network)
+                .map(|(_, n)| n)
+                .unwrap();
+            let sf = {
+                let router = router.clone();
+                let name = connection.network.clone();
+                move |target: String, msg| router.send(Some(&name), &target, msg)
+            };
+            let (settings_tx, settings_rx) = watch::channel(Arc::new(BotSettings::new(
+                &cfg,
+                &connection.network,
+                network,
+            )));
+            settings.insert(connection.network.clone(), settings_tx);
+            let mut bot = Bot::new(
+                settings_rx,
+                db_conn.clone(),
+                opt_outs.clone(),
+                histories.clone(),
+                cfg.bot.history_depth,
+                sf,
+                Duration::from_secs(cfg.bot.report_cooldown.unwrap_or(600)),
+            );
+            for (name, cmd) in &self.commands {
+                bot.insert_command(name.clone(), cmd.clone());
+            }
+            for (name, regex, trig) in &self.triggers {
+                bot.insert_trigger(name.clone(), regex.clone(), trig.clone());
+            }
+            let task = tokio::spawn(transport::message_loop(connection.messages, bot));
+            loops.push((connection.network, task));
+        }
+        let qotd_task = cfg.qotd.clone().map(|qotd_cfg| {
+            let settings = settings
+                .iter()
+                .map(|(network, tx)| (network.clone(), tx.subscribe()))
+                .collect();
+            let task = qotd::run_scheduler(
+                qotd_cfg,
+                db_conn.clone(),
+                settings,
+                router.clone(),
+                ctx.subscribe(),
+            );
+            tokio::spawn(async move {
+                if let Err(e) = task.await {
+                    tracing::error!("Fatal error in the quote of the day scheduler: {}", e);
+                }
+            })
+        });
+        drop(db_conn);
+        let mut reloader = self
+            .config_path
+            .map(|path| Reloader::new(path, cfg, settings, webhooks_tx, router.clone()));
+
+        tokio::pin!(shutdown);
+        let finished = loop {
+            select! {
+                () = &mut shutdown => {
+                    tracing::info!("Received shutdown signal, sending QUIT message");
+                    break None;
+                }
+                () = reload_signal.recv() => {
+                    if let Some(reloader) = &mut reloader {
+                        reloader.reload();
+                    } else {
+                        tracing::warn!("Received SIGHUP, but the config path is unknown");
+                    }
+                }
+                (result, index, _) = future::select_all(loops.iter_mut().map(|(_, t)| t)) => {
+                    break Some((index, result));
+                }
+            }
+        };
+        if let Some((index, result)) = finished {
+            let (network, _) = loops.remove(index);
+            log_loop_result(&network, result?);
+        }
+        router.quit("überbot shutting down");
+        for (network, task) in loops {
+            log_loop_result(&network, task.await?);
+        }
+
+        tracing::info!("Closing services...");
+        let _ = ctx.send(());
+        #[cfg(feature = "web")]
+        if let Some(t) = http_task {
+            t.await.unwrap();
+            tracing::info!("Web service finished");
+        }
+        if let Some(t) = qotd_task {
+            t.await.unwrap();
+        }
+        exec_thread.join().unwrap();
+        tracing::info!("DB Executor thread finished");
+        tracing::info!("Shutdown complete!");
+
+        Ok(())
+    }
+}
+
+struct Connection {
+    network: String,
+    transport: Arc<dyn Transport>,
+    messages: MessageStream,
+}
+
+fn log_loop_result(network: &str, result: anyhow::Result<()>) {
+    match result {
+        Ok(()) => tracing::info!("Message loop for network '{}' finished", network),
+        Err(e) => tracing::error!(
+            "An error has occurred on network '{}', shutting down: {}",
+            network,
+            e
+        ),
+    }
+}
@@ -0,0 +1,117 @@
+use std::path::PathBuf;
+
+pub const USAGE: &str = "\
+Usage: uberbot [OPTIONS] [COMMAND]
+
+Commands:
+  run                 Connect to IRC and run the bot (default)
+  check-config        Validate the config and the database without connecting to IRC
+  db stats            Show database statistics
+  db vacuum           Reclaim unused space in the database
+  db backup <PATH>    Write a copy of the database to PATH
+  db export <PATH>    Export all quotes to a .json or .csv file
+  db import <PATH>    Import quotes from a .json or .csv file, skipping duplicates
+  db import-log <PATH> [CHAN]
+                      Import quotes from an irssi, weechat or ZNC log excerpt,
+                      quotes are separated by empty lines
+
+Options:
+  -c, --config <PATH>    Config file (default: $UBERBOT_CONFIG or uberbot.toml)
+      --console          Read messages from stdin instead of connecting to IRC
+      --nick <NICK>      Nick used in console mode (default: console)
+      --channel <CHAN>   Channel used in console mode (default: the first configured channel)
+  -V, --version          Print version
+  -h, --help             Print help";
+
+pub enum Command {
+    Run { console: Option<ConsoleArgs> },
+    CheckConfig,
+    Db(DbCommand),
+    Help,
+    Version,
+}
+
+#[derive(Default)]
+pub struct ConsoleArgs {
+    pub nick: Option<String>,
+    pub channel: Option<String>,
+}
+
+pub enum DbCommand {
+    Stats,
+    Vacuum,
+    Backup(PathBuf),
+    Export(PathBuf),
+    Import(PathBuf),
+    ImportLog {
+        path: PathBuf,
+        channel: Option<String>,
+    },
+}
+
+pub struct Args {
+    pub config: Option<PathBuf>,
+    pub command: Command,
+}
+
+impl Args {
+    pub fn parse(mut args: impl Iterator<Item = String>) -> anyhow::Result<Self> {
+        let mut config = None;
+        let mut console: Option<ConsoleArgs> = None;
+        let mut positional = Vec::new();
+        while let Some(arg) = args.next() {
+            let mut value = |name: &str| {
+                args.next()
+                    .ok_or_else(|| anyhow::anyhow!("Missing value for {name}"))
+            };
+            match arg.as_str() {
+                "-h" | "--help" => return Ok(Self::only(Command::Help)),
+                "-V" | "--version" => return Ok(Self::only(Command::Version)),
+                "-c" | "--config" => config = Some(PathBuf::from(value(&arg)?)),
+                "--console" => {
+                    console.get_or_insert_with(ConsoleArgs::default);
+                }
+                "--nick" => {
+                    console.get_or_insert_with(ConsoleArgs::default).nick = Some(value(&arg)?);
+                }
+                "--channel" => {
+                    console.get_or_insert_with(ConsoleArgs::default).channel = Some(value(&arg)?);
+                }
+                _ if arg.starts_with('-') => anyhow::bail!("Unknown option: {arg}"),
+                _ => positional.push(arg),
+            }
+        }
+        let positional: Vec<&str> = positional.iter().map(String::as_str).collect();
+        let command = match positional.as_slice() {
+            [] | ["run"] => Command::Run { console },
+            _ if console.is_some() => anyhow::bail!("Console options can only be used with 'run'"),
+            ["check-config"] => Command::CheckConfig,
+            ["db", "stats"] => Command::Db(DbCommand::Stats),
+            ["db", "vacuum"] => Command::Db(DbCommand::Vacuum),
+            ["db", "backup", path] => Command::Db(DbCommand::Backup(PathBuf::from(path))),
+            ["db", "export", path] => Command::Db(DbCommand::Export(PathBuf::from(path))),
+            ["db", "import", path] => Command::Db(DbCommand::Import(PathBuf::from(path))),
+            ["db", "import-log", path, channel @ ..] if channel.len() <= 1 => {
+                Command::Db(DbCommand::ImportLog {
+                    path: PathBuf::from(path),
+                    channel: channel.first().map(|c| (*c).to_string()),
+                })
+            }
+            ["db", cmd @ ("backup" | "export" | "import" | "import-log")] => {
+                anyhow::bail!("Missing {cmd} path")
+            }
+            ["db", ..] => anyhow::bail!(
+                "Unknown database command, expected stats, vacuum, backup, export, import or import-log"
+            ),
+            _ => anyhow::bail!("Unknown command: {}", positional.join(" ")),
+        };
+        Ok(Self { config, command })
+    }
+
+    fn only(command: Command) -> Self {
+        Self {
+            config: None,
+            command,
+        }
+    }
+}
@@ -12,7 +12,7 @@ impl Command for LastMsg {
         Ok(format!(
             "{}: {:?}",
             nick,
-            msg.history.last_msgs(&nick, usize::MAX).await
+            msg.history.last_msgs(&msg.channel, &nick, usize::MAX).await
         ))
     }
 }
@@ -28,6 +28,6 @@ impl Command for Sleep {
             return Ok("Invalid usage.".to_string());
         };
         sleep(Duration::from_secs(duration)).await;
-        return Ok(format!("Slept {} seconds", duration));
+        Ok(format!("Slept {duration} seconds"))
     }
 }
@@ -1,4 +1,7 @@
-use crate::bot::{Command, CommandContext};
+use crate::{
+    bot::{Command, CommandContext},
+    error::UserError,
+};
 use async_trait::async_trait;
 use std::collections::HashMap;
 use tokio::sync::Mutex;
@@ -15,7 +18,8 @@ impl Command for Eval {
             let mut last_eval = self.last_eval.lock().await;
             let last_eval = last_eval.entry(msg.author).or_insert(0.0);
             let mut meval_ctx = meval::Context::new();
-            let value = meval::eval_str_with_context(&expr, meval_ctx.var("x", *last_eval))?;
+            let value = meval::eval_str_with_context(&expr, meval_ctx.var("x", *last_eval))
+                .map_err(|e| UserError::new(format!("Invalid expression: {e}")))?;
             *last_eval = value;
             Ok(format!("{expr} = {value:.10}"))
         } else {
@@ -1,21 +1,65 @@
 use crate::bot::{Command, CommandContext};
 use async_trait::async_trait;
 
-const HELP: &str = concat!(
-    "=- \x1d\x02Überbot\x0f ",
-    env!("CARGO_PKG_VERSION"),
-    " -=\r\n",
-    " * waifu <category>      * grab [count] <user>\r\n",
-    " * owo/mock/leet [user]  * quot <user>\r\n",
-    " * ev <math expression>  * qsearch <query>\r\n",
-    " - This bot can also resolve HTML titles, Spotify links and a subset of sed expressions."
-);
+/// Entries of the command list, along with whether the command is compiled in
+const COMMANDS: &[(&str, bool)] = &[
+    ("waifu <category>", cfg!(feature = "waifu")),
+    ("grab [count] <user> [\"text\" or /regex/]", true),
+    ("owo/mock/leet [user]", true),
+    ("quot [-c chan] [-v] [user] [query]", true),
+    ("ev <math expression>", cfg!(feature = "eval")),
+    ("qsearch <query>", true),
+    ("quote/unquote <id>", true),
+    ("ungrab", true),
+    ("qstats [user]", true),
+    ("optout/optin/forgetme", true),
+    ("+1/-1 <id>", true),
+    ("qtop [user]", true),
+    ("convo [count] [users]", true),
+    ("qnext/qprev/qpage <n>", true),
+    ("qotd", true),
+];
+
+/// Things resolved without a command, along with whether the resolver is compiled in
+const RESOLVERS: &[(&str, bool)] = &[
+    ("HTML titles", cfg!(feature = "title")),
+    ("Spotify links", cfg!(feature = "spotify")),
+    ("a subset of sed expressions", true),
+];
+
+/// Maximum length of a line listing commands
+const LINE_WIDTH: usize = 80;
+
+fn help() -> String {
+    let mut lines = vec![format!(
+        "=- \x1d\x02Überbot\x0f {} -=",
+        env!("CARGO_PKG_VERSION")
+    )];
+    // the commands are packed into as few lines as possible, they don't all fit in columns
+    let mut line = String::new();
+    for (command, _) in COMMANDS.iter().filter(|c| c.1) {
+        if !line.is_empty() && line.len() + command.len() + 3 > LINE_WIDTH {
+            lines.push(std::mem::take(&mut line));
+        }
+        line += " * ";
+        line += command;
+    }
+    lines.push(line);
+    let resolvers: Vec<&str> = RESOLVERS.iter().filter(|r| r.1).map(|r| r.0).collect();
+    let resolvers = match resolvers.split_last() {
+        Some((last, [])) => (*last).to_string(),
+        Some((last, rest)) => format!("{} and {last}", rest.join(", ")),
+        None => unreachable!(),
+    };
+    lines.push(format!(" - This bot can also resolve {resolvers}."));
+    lines.join("\r\n")
+}
 
 pub struct Help;
 
 #[async_trait]
 impl Command for Help {
     async fn execute(&self, _msg: CommandContext) -> anyhow::Result<String> {
-        Ok(HELP.into())
+        Ok(help())
     }
 }
@@ -1,11 +1,17 @@
 #[cfg(feature = "debug")]
 pub mod debug;
 
+#[cfg(feature = "eval")]
 pub mod eval;
 pub mod help;
 pub mod leek;
+pub mod privacy;
+pub mod qotd;
 pub mod quotes;
 pub mod sed;
+#[cfg(feature = "spotify")]
 pub mod spotify;
+#[cfg(feature = "title")]
 pub mod title;
+#[cfg(feature = "waifu")]
 pub mod waifu;
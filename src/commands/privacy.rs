@@ -0,0 +1,85 @@
+use crate::bot::{Command, CommandContext};
+use async_trait::async_trait;
+use std::{collections::HashMap, sync::Mutex, time::Instant};
+
+/// Stops the bot from storing and quoting the user's messages
+pub struct OptOut;
+pub struct OptIn;
+
+/// Deletes the user's quotes and history, after they confirm it.
+/// Admins can make the bot forget other users without confirmation.
+#[derive(Default)]
+pub struct ForgetMe {
+    /// Users who were asked to confirm, and when
+    pending: Mutex<HashMap<String, Instant>>,
+}
+
+/// How long users have to confirm `forgetme`, in seconds
+const CONFIRM_WINDOW: u64 = 60;
+
+#[async_trait]
+impl Command for OptOut {
+    async fn execute(&self, msg: CommandContext) -> anyhow::Result<String> {
+        msg.histories.forget(&msg.author).await;
+        msg.opt_outs.insert(&msg.author);
+        if msg.db.set_opt_out(msg.author, true).await? {
+            Ok("Your messages won't be stored or quoted anymore. Use 'optin' to undo this.".into())
+        } else {
+            Ok("You've already opted out.".into())
+        }
+    }
+}
+
+#[async_trait]
+impl Command for OptIn {
+    async fn execute(&self, msg: CommandContext) -> anyhow::Result<String> {
+        msg.opt_outs.remove(&msg.author);
+        if msg.db.set_opt_out(msg.author, false).await? {
+            Ok("Your messages can be quoted again.".into())
+        } else {
+            Ok("You haven't opted out.".into())
+        }
+    }
+}
+
+#[async_trait]
+impl Command for ForgetMe {
+    async fn execute(&self, msg: CommandContext) -> anyhow::Result<String> {
+        let target = msg
+            .content
+            .as_deref()
+            .map(str::trim)
+            .filter(|t| !t.is_empty());
+        let nick = match target {
+            Some(target) if !target.eq_ignore_ascii_case(&msg.author) => {
+                if !msg.is_admin {
+                    return Ok("Only admins can make me forget other users.".into());
+                }
+                target.to_string()
+            }
+            _ => {
+                let confirmed = {
+                    let mut pending = self.pending.lock().unwrap();
+                    pending.retain(|_, asked| asked.elapsed().as_secs() < CONFIRM_WINDOW);
+                    let confirmed = pending.remove(&msg.author).is_some();
+                    if !confirmed {
+                        pending.insert(msg.author.clone(), Instant::now());
+                    }
+                    confirmed
+                };
+                if !confirmed {
+                    // quotes are forgotten in every channel
+                    let quotes = msg.db.user_stats(msg.author, None).await?.quotes;
+                    return Ok(format!(
+                        "This will delete your {quotes} quotes and your message history, and I won't store your messages anymore. Use 'forgetme' again within {CONFIRM_WINDOW} seconds to confirm."
+                    ));
+                }
+                msg.author
+            }
+        };
+        msg.histories.forget(&nick).await;
+        msg.opt_outs.insert(&nick);
+        let deleted = msg.db.forget_user(nick.clone()).await?;
+        Ok(format!("Forgot {nick}, {deleted} quotes were deleted."))
+    }
+}
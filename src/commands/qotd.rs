@@ -0,0 +1,131 @@
+use crate::{
+    bot::{BotSettings, Command, CommandContext},
+    commands::quotes::format_quote,
+    config::QotdConfig,
+    database::{unix_now, ExecutorConnection, Quote},
+    formatting::strip_formatting,
+    transport::{split_target, Router},
+};
+use async_trait::async_trait;
+use std::{collections::HashMap, sync::Arc, time::Duration};
+use tokio::{
+    select,
+    sync::{broadcast, watch},
+};
+
+/// Shows the quote of the day of the channel, picking one if it wasn't posted yet
+pub struct Qotd {
+    /// Time the quote of the day changes, in seconds since midnight (UTC)
+    time_of_day: u64,
+    /// How long it takes until a quote can be picked again in the same channel, in seconds
+    repeat_window: u64,
+}
+
+/// How many days have to pass before a quote of the day is repeated, if it's not configured
+const DEFAULT_REPEAT_WINDOW: u64 = 30;
+
+impl Qotd {
+    pub fn new(cfg: Option<&QotdConfig>) -> anyhow::Result<Self> {
+        Ok(Self {
+            time_of_day: cfg.map(QotdConfig::time_of_day).transpose()?.unwrap_or(0),
+            repeat_window: repeat_window(cfg),
+        })
+    }
+}
+
+#[async_trait]
+impl Command for Qotd {
+    async fn execute(&self, msg: CommandContext) -> anyhow::Result<String> {
+        let since = day_start(unix_now(), self.time_of_day);
+        let quote = msg
+            .db
+            .quote_of_the_day(
+                msg.quote_channels[0].clone(),
+                msg.quote_channels,
+                since,
+                self.repeat_window,
+            )
+            .await?;
+        Ok(format_qotd(quote.as_ref()))
+    }
+}
+
+/// Posts the quote of the day to the configured channels every day at the configured time,
+/// until `shutdown` receives a message
+pub(crate) async fn run_scheduler(
+    cfg: QotdConfig,
+    db: ExecutorConnection,
+    settings: HashMap<String, watch::Receiver<Arc<BotSettings>>>,
+    router: Arc<Router>,
+    mut shutdown: broadcast::Receiver<()>,
+) -> anyhow::Result<()> {
+    let time_of_day = cfg.time_of_day()?;
+    let repeat_window = repeat_window(Some(&cfg));
+    loop {
+        let now = unix_now();
+        let next = day_start(now, time_of_day) + 86400;
+        let wait = Duration::from_secs(u64::try_from(next - now).unwrap_or(0));
+        tracing::debug!("Next quote of the day in {}s", wait.as_secs());
+        select! {
+            () = tokio::time::sleep(wait) => {}
+            _ = shutdown.recv() => return Ok(()),
+        }
+        for target in &cfg.channels {
+            let (network, channel) = split_target(target);
+            // unqualified channels are only allowed with a single network
+            let settings = match network {
+                Some(network) => settings.get(network),
+                None => settings.values().next(),
+            };
+            let Some(settings) = settings.map(|s| Arc::clone(&s.borrow())) else {
+                continue;
+            };
+            let quote = db
+                .quote_of_the_day(
+                    settings.quote_channel(channel),
+                    settings.quote_channels(channel),
+                    next,
+                    repeat_window,
+                )
+                .await;
+            let quote = match quote {
+                Ok(Some(quote)) => quote,
+                Ok(None) => {
+                    tracing::info!("No quote of the day left for '{}'", target);
+                    continue;
+                }
+                Err(e) => {
+                    tracing::error!("Couldn't pick the quote of the day for '{}': {}", target, e);
+                    continue;
+                }
+            };
+            let mut msg = format_qotd(Some(&quote));
+            if !settings.color(channel) {
+                msg = strip_formatting(&msg);
+            }
+            if let Err(e) = router.send(network, channel, msg) {
+                tracing::warn!("Couldn't post the quote of the day to '{}': {}", target, e);
+            }
+        }
+    }
+}
+
+fn format_qotd(quote: Option<&Quote>) -> String {
+    match quote {
+        Some(q) => format!("Quote of the day: {}", format_quote(q)),
+        None => "There's no quote of the day, all quotes were posted recently.".into(),
+    }
+}
+
+/// Returns the repeat window in seconds
+fn repeat_window(cfg: Option<&QotdConfig>) -> u64 {
+    cfg.and_then(|c| c.repeat_window)
+        .unwrap_or(DEFAULT_REPEAT_WINDOW)
+        .saturating_mul(86400)
+}
+
+/// Returns the timestamp of the last time the quote of the day changed
+fn day_start(now: i64, time_of_day: u64) -> i64 {
+    let time_of_day = i64::try_from(time_of_day).unwrap_or(0);
+    (now - time_of_day).div_euclid(86400) * 86400 + time_of_day
+}
@@ -1,43 +1,286 @@
 use crate::{
     bot::{Command, CommandContext},
-    database::Quote,
+    database::{AddedQuote, Page, Quote, QuoteFilter, QuoteLine, SearchResult},
+    error::UserError,
+    transport::split_target,
 };
 use async_trait::async_trait;
+use regex::{Regex, RegexBuilder};
 use std::fmt::Write;
 
 pub struct Grab;
-pub struct Quot;
+/// Grabs an interleaved conversation from the channel history
+pub struct Convo;
+pub struct QuoteById;
+pub struct Unquote;
+/// Shows who's quoted the most and who grabs the most, or the statistics of a user
+pub struct Stats;
 
-pub struct Search {
+/// Number of users listed by [`Stats`]
+const STATS_LIMIT: usize = 3;
+
+pub struct Quot {
+    /// Whether quotes with a higher score are picked more often
+    weighted: bool,
+}
+
+impl Quot {
+    pub fn new(weighted: bool) -> Self {
+        Self { weighted }
+    }
+}
+
+/// Upvotes or downvotes a quote
+pub struct Vote {
+    vote: i64,
+}
+
+impl Vote {
+    pub fn up() -> Self {
+        Self { vote: 1 }
+    }
+
+    pub fn down() -> Self {
+        Self { vote: -1 }
+    }
+}
+
+pub struct Top {
     limit: usize,
 }
 
-impl Search {
+impl Top {
     pub fn new(limit: usize) -> Self {
         Self { limit }
     }
 }
 
-pub struct SearchNext {
+pub struct Ungrab {
+    /// How long after grabbing a quote it can be removed, in seconds
+    window: u64,
+}
+
+impl Ungrab {
+    pub fn new(window: u64) -> Self {
+        Self { window }
+    }
+}
+
+pub struct Search {
     limit: usize,
 }
 
-impl SearchNext {
+impl Search {
     pub fn new(limit: usize) -> Self {
         Self { limit }
     }
 }
 
+/// Shows another page of the last search, `page` is `None` if the page number is given by the user
+pub struct SearchPage {
+    page: Option<Page>,
+    limit: usize,
+}
+
+impl SearchPage {
+    pub fn next(limit: usize) -> Self {
+        Self {
+            page: Some(Page::Next),
+            limit,
+        }
+    }
+
+    pub fn previous(limit: usize) -> Self {
+        Self {
+            page: Some(Page::Previous),
+            limit,
+        }
+    }
+
+    pub fn jump(limit: usize) -> Self {
+        Self { page: None, limit }
+    }
+}
+
+pub(crate) fn format_quote(q: &Quote) -> String {
+    format_quote_text(q, &q.quote)
+}
+
+/// Formats `q` with `text` in place of its content
+fn format_quote_text(q: &Quote, text: &str) -> String {
+    let id = q.id.map(|id| format!(" (#{id})")).unwrap_or_default();
+    if q.lines.is_empty() {
+        format!("\"{text}\" ~{}{id}", q.author)
+    } else {
+        format!("{text}{id}")
+    }
+}
+
+/// Formats a page of search results, `page` starts from 0
+fn format_search_page(results: &[SearchResult], page: usize, limit: usize) -> String {
+    if results.is_empty() {
+        return "No results.".into();
+    }
+    let mut buf = String::new();
+    for r in results {
+        let _ = write!(buf, "{}\r\n", format_quote_text(&r.quote, &r.snippet));
+    }
+    if results.len() == limit {
+        let _ = write!(buf, "Page {}, use 'qnext' for more results.", page + 1);
+    } else if page > 0 {
+        let _ = write!(buf, "Page {}.", page + 1);
+    }
+    buf.trim_end().into()
+}
+
+/// Parses a search query, which can contain `author:<user>`, `after:<date>` and `before:<date>` filters
+fn parse_search(query: &str) -> Result<QuoteFilter, UserError> {
+    let mut filter = QuoteFilter::default();
+    let mut terms = Vec::new();
+    for word in query.split_whitespace() {
+        if let Some(author) = word.strip_prefix("author:") {
+            filter.author = Some(author.into());
+        } else if let Some(date) = word.strip_prefix("after:") {
+            filter.after = Some(parse_date(date)?);
+        } else if let Some(date) = word.strip_prefix("before:") {
+            filter.before = Some(parse_date(date)?);
+        } else {
+            terms.push(word);
+        }
+    }
+    filter.query = fts_query(&terms);
+    Ok(filter)
+}
+
+/// Quotes every search term, so punctuation isn't parsed as FTS5 query syntax.
+/// Returns `None` if there are no terms.
+fn fts_query(terms: &[&str]) -> Option<String> {
+    if terms.is_empty() {
+        return None;
+    }
+    let quoted: Vec<String> = terms
+        .iter()
+        .map(|t| format!("\"{}\"", t.replace('"', "\"\"")))
+        .collect();
+    Some(quoted.join(" "))
+}
+
+/// Checks that `date` is a `YYYY-MM-DD` date
+fn parse_date(date: &str) -> Result<String, UserError> {
+    let parts: Vec<&str> = date.split('-').collect();
+    let valid = matches!(parts.as_slice(), [y, m, d]
+        if y.len() == 4 && m.len() == 2 && d.len() == 2
+            && date.chars().all(|c| c.is_ascii_digit() || c == '-')
+            && matches!(m.parse::<u8>(), Ok(1..=12))
+            && matches!(d.parse::<u8>(), Ok(1..=31)));
+    if valid {
+        Ok(date.into())
+    } else {
+        Err(UserError::new("Invalid date, use YYYY-MM-DD."))
+    }
+}
+
+/// Like [`format_quote`], followed by who grabbed the quote and when, if it's known
+fn format_quote_details(q: &Quote) -> String {
+    let mut buf = format_quote(q);
+    if let Some(grabbed_by) = &q.grabbed_by {
+        let _ = write!(buf, ", grabbed by {grabbed_by}");
+    }
+    if let Some(timestamp) = q.timestamp {
+        let _ = write!(buf, " on {}", format_date(timestamp));
+    }
+    buf
+}
+
+/// Formats a unix timestamp as a `YYYY-MM-DD` date in UTC
+fn format_date(timestamp: i64) -> String {
+    // http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let z = timestamp.div_euclid(86400) + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!("{year:04}-{month:02}-{day:02}")
+}
+
+/// Checks if a quote was grabbed in one of `channels`, quotes without a channel are in every channel
+fn in_channels(q: &Quote, channels: &[String]) -> bool {
+    match &q.channel {
+        Some(c) => channels.iter().any(|ch| ch.eq_ignore_ascii_case(c)),
+        None => true,
+    }
+}
+
+/// Parses a quote id, optionally prefixed with `#`
+fn parse_id(arg: Option<&str>) -> Result<i64, UserError> {
+    arg.map(|a| a.trim().trim_start_matches('#'))
+        .and_then(|a| a.parse().ok())
+        .ok_or_else(|| UserError::new("Invalid quote id."))
+}
+
+/// Picks the message to grab, given as `"substring"` or `/regex/` (optionally followed by `i`)
+enum MessageFilter {
+    Substring(String),
+    Regex(Regex),
+}
+
+impl MessageFilter {
+    fn parse(arg: &str) -> Option<Result<Self, UserError>> {
+        if let Some(text) = arg.strip_prefix('"').and_then(|a| a.strip_suffix('"')) {
+            return Some(Ok(Self::Substring(text.to_lowercase())));
+        }
+        let arg = arg.strip_prefix('/')?;
+        let (pattern, ignore_case) = if let Some(p) = arg.strip_suffix("/i") {
+            (p, true)
+        } else {
+            (arg.strip_suffix('/')?, false)
+        };
+        Some(
+            RegexBuilder::new(pattern)
+                .case_insensitive(ignore_case)
+                .build()
+                .map(Self::Regex)
+                .map_err(|_| UserError::new("Invalid regular expression.")),
+        )
+    }
+
+    fn matches(&self, message: &str) -> bool {
+        match self {
+            Self::Substring(text) => message.to_lowercase().contains(text),
+            Self::Regex(re) => re.is_match(message),
+        }
+    }
+}
+
 #[async_trait]
 impl Command for Grab {
     async fn execute(&self, msg: CommandContext) -> anyhow::Result<String> {
         let Some(content) = msg.content else { return Ok("Invalid usage.".into()); };
-        let mut split = content.splitn(2, ' ');
-        let split = (split.next().unwrap(), split.next());
-        let (author, count) = if let Some(author) = split.1 {
-            (author, split.0.parse::<usize>()?)
-        } else {
-            (split.0, 1)
+        let (first, rest) = match content.trim().split_once(' ') {
+            Some((first, rest)) => (first, Some(rest.trim())),
+            None => (content.trim(), None),
+        };
+        let (count, author, filter) = match rest.map(|r| (r, MessageFilter::parse(r))) {
+            Some((_, Some(filter))) => (1, first, Some(filter?)),
+            Some((rest, None)) => {
+                let count = first
+                    .parse::<usize>()
+                    .map_err(|_| UserError::new("Invalid message count."))?;
+                match rest.split_once(' ') {
+                    Some((author, filter)) => {
+                        let Some(filter) = MessageFilter::parse(filter.trim()) else {
+                            return Ok("Invalid usage.".into());
+                        };
+                        (count, author, Some(filter?))
+                    }
+                    None => (count, rest, None),
+                }
+            }
+            None => (1, first, None),
         };
         if count == 0 {
             return Ok("So are you going to grab anything?".into());
@@ -45,18 +288,88 @@ impl Command for Grab {
         if author == msg.author {
             return Ok("You can't grab yourself.".into());
         }
-        let messages = msg.history.last_msgs(author, count).await;
-        if let Some(messages) = messages {
-            let message = messages.join(" | ");
-            msg.db
-                .add_quote(Quote {
-                    author: author.into(),
-                    quote: message,
-                })
-                .await?;
-            Ok(format!("Quote added ({} messages).", messages.len()))
+        if msg.opt_outs.contains(author) {
+            return Ok(format!("{author} doesn't want to be quoted."));
+        }
+        let messages = if let Some(filter) = &filter {
+            let messages = msg
+                .history
+                .last_msgs_matching(&msg.channel, author, count, |m| filter.matches(m))
+                .await;
+            let Some(messages) = messages else {
+                return Ok("No matching message to grab.".into());
+            };
+            messages
         } else {
-            Ok("No previous messages to grab.".into())
+            let Some(messages) = msg.history.last_msgs(&msg.channel, author, count).await else {
+                return Ok("No previous messages to grab.".into());
+            };
+            messages
+        };
+        let added = msg
+            .db
+            .add_quote(
+                Quote {
+                    author: author.into(),
+                    quote: messages.join(" | "),
+                    channel: msg.quote_channels.first().cloned(),
+                    grabbed_by: Some(msg.author),
+                    ..Quote::default()
+                },
+                Some(msg.quote_channels),
+            )
+            .await?;
+        match added {
+            AddedQuote::Added(id) => Ok(format!("Quote #{id} added ({} messages).", messages.len())),
+            AddedQuote::Duplicate(id) => Ok(format!("This was already quoted as #{id}.")),
+        }
+    }
+}
+
+#[async_trait]
+impl Command for Convo {
+    async fn execute(&self, msg: CommandContext) -> anyhow::Result<String> {
+        let Some(content) = msg.content else { return Ok("Invalid usage.".into()); };
+        let mut args: Vec<&str> = content.split_whitespace().collect();
+        let count = match args.first().map(|a| a.parse::<usize>()) {
+            Some(Ok(count)) => {
+                args.remove(0);
+                Some(count)
+            }
+            Some(Err(_)) => None,
+            None => return Ok("Invalid usage.".into()),
+        };
+        let messages = match count {
+            Some(0) => return Ok("So are you going to grab anything?".into()),
+            Some(count) => msg.history.last_channel_msgs(&msg.channel, count, &args).await,
+            // without a count, the last message of each named user is grabbed
+            None => msg.history.last_channel_msg_of_each(&msg.channel, &args).await,
+        };
+        if messages.is_empty() {
+            return Ok("No previous messages to grab.".into());
+        }
+        if messages.iter().all(|(author, _)| *author == msg.author) {
+            return Ok("You can't grab yourself.".into());
+        }
+        let count = messages.len();
+        let lines = messages
+            .into_iter()
+            .map(|(speaker, message)| QuoteLine { speaker, message })
+            .collect();
+        let added = msg
+            .db
+            .add_quote(
+                Quote {
+                    channel: msg.quote_channels.first().cloned(),
+                    grabbed_by: Some(msg.author),
+                    ..Quote::conversation(lines)
+                },
+                Some(msg.quote_channels),
+            )
+            .await?;
+        match added {
+            AddedQuote::Added(id) => Ok(format!("Quote #{id} added ({count} messages).")),
+            AddedQuote::Duplicate(id) => Ok(format!("This was already quoted as #{id}.")),
         }
     }
 }
@@ -64,50 +377,190 @@ impl Command for Grab {
 #[async_trait]
 impl Command for Quot {
     async fn execute(&self, msg: CommandContext) -> anyhow::Result<String> {
-        let author = msg.content;
-        if let Some(q) = msg.db.get_quote(author).await? {
-            Ok(format!("\"{}\" ~{}", q.quote, q.author))
-        } else {
-            Ok("No quotes found from this user.".into())
+        let content = msg.content.unwrap_or_default();
+        let mut args = content.split_whitespace().peekable();
+        let mut filter = QuoteFilter::default();
+        let mut details = false;
+        while let Some(flag) = args.next_if(|a| a.starts_with('-')) {
+            match flag {
+                "-c" => {
+                    let Some(channel) = args.next() else { return Ok("Invalid usage.".into()); };
+                    let name = split_target(channel).1;
+                    let qualified = match split_target(channel).0 {
+                        Some(_) => channel.to_string(),
+                        None => format!("{}/{channel}", msg.network),
+                    };
+                    filter.channels = Some(vec![qualified, name.into()]);
+                }
+                "-v" => details = true,
+                _ => return Ok("Invalid usage.".into()),
+            }
+        }
+        filter.author = args.next().map(Into::into);
+        filter.query = fts_query(&args.collect::<Vec<_>>());
+        let searched = filter.query.is_some() || filter.channels.is_some();
+        filter.channels.get_or_insert(msg.quote_channels);
+        match msg.db.get_quote(filter, self.weighted).await? {
+            Some(q) if details => Ok(format_quote_details(&q)),
+            Some(q) => Ok(format_quote(&q)),
+            None if searched => Ok("No matching quotes found.".into()),
+            None => Ok("No quotes found from this user.".into()),
         }
     }
 }
 
 #[async_trait]
-impl Command for Search {
+impl Command for QuoteById {
     async fn execute(&self, msg: CommandContext) -> anyhow::Result<String> {
-        let Some(query) = msg.content else { return Ok("Invalid usage.".into()); };
-        let results = msg.db.search_quotes(msg.author, query, self.limit).await?;
-        if results.is_empty() {
-            return Ok("No results.".into());
+        let id = parse_id(msg.content.as_deref())?;
+        match msg.db.get_quote_by_id(id).await? {
+            Some(q) if in_channels(&q, &msg.quote_channels) => Ok(format_quote(&q)),
+            _ => Ok(format!("Quote #{id} doesn't exist.")),
         }
-        let mut buf = String::new();
-        for q in &results {
-            write!(buf, "\"{}\" ~{}\r\n", q.quote, q.author)?;
+    }
+}
+
+#[async_trait]
+impl Command for Unquote {
+    async fn execute(&self, msg: CommandContext) -> anyhow::Result<String> {
+        let id = parse_id(msg.content.as_deref())?;
+        let quote = msg.db.get_quote_by_id(id).await?;
+        let Some(q) = quote.filter(|q| in_channels(q, &msg.quote_channels)) else {
+            return Ok(format!("Quote #{id} doesn't exist."));
+        };
+        let is_grabber =
+            matches!(q.grabbed_by.as_deref(), Some(g) if g.eq_ignore_ascii_case(&msg.author));
+        if !is_grabber && !msg.is_admin {
+            return Ok("Only the user who grabbed this quote or an admin can remove it.".into());
+        }
+        msg.db.delete_quote(id).await?;
+        Ok(format!("Quote #{id} removed."))
+    }
+}
+
+#[async_trait]
+impl Command for Vote {
+    async fn execute(&self, msg: CommandContext) -> anyhow::Result<String> {
+        let id = parse_id(msg.content.as_deref())?;
+        let quote = msg.db.get_quote_by_id(id).await?;
+        if !matches!(&quote, Some(q) if in_channels(q, &msg.quote_channels)) {
+            return Ok(format!("Quote #{id} doesn't exist."));
         }
-        if results.len() == self.limit {
-            buf.push_str("Use 'qnext' for more results.");
+        if let Some(score) = msg.db.vote(id, msg.author, self.vote).await? {
+            Ok(format!("Quote #{id} now has a score of {score:+}."))
+        } else {
+            Ok(format!("Quote #{id} doesn't exist."))
         }
-        Ok(buf)
     }
 }
 
 #[async_trait]
-impl Command for SearchNext {
+impl Command for Top {
     async fn execute(&self, msg: CommandContext) -> anyhow::Result<String> {
-        let Some(results) = msg.db.advance_search(msg.author, self.limit).await? else {
-            return Ok("You need to initiate a search first using 'qsearch'.".into());
+        let filter = QuoteFilter {
+            author: msg.content,
+            channels: Some(msg.quote_channels),
+            ..QuoteFilter::default()
         };
+        let results = msg.db.top_quotes(filter, self.limit).await?;
         if results.is_empty() {
-            return Ok("No results.".into());
+            return Ok("No quotes found.".into());
         }
         let mut buf = String::new();
         for q in &results {
-            write!(buf, "\"{}\" ~{}\r\n", q.quote, q.author)?;
+            write!(buf, "{} {:+}\r\n", format_quote(q), q.score)?;
         }
-        if results.len() == self.limit {
-            buf.push_str("Use 'qnext' again for more results.");
+        Ok(buf.trim_end().into())
+    }
+}
+
+#[async_trait]
+impl Command for Ungrab {
+    async fn execute(&self, msg: CommandContext) -> anyhow::Result<String> {
+        let channel = msg.quote_channels[0].clone();
+        if let Some(q) = msg.db.undo_grab(msg.author, channel, self.window).await? {
+            Ok(format!("Removed {}", format_quote(&q)))
+        } else {
+            Ok("You haven't grabbed anything recently.".into())
+        }
+    }
+}
+
+fn format_ranking(ranking: &[(String, u64)]) -> String {
+    ranking
+        .iter()
+        .map(|(user, n)| format!("{user} ({n})"))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+#[async_trait]
+impl Command for Stats {
+    async fn execute(&self, msg: CommandContext) -> anyhow::Result<String> {
+        if let Some(user) = msg.content.as_deref().map(str::trim).filter(|u| !u.is_empty()) {
+            let stats = msg.db.user_stats(user.into(), Some(msg.quote_channels)).await?;
+            let mut buf = format!(
+                "{user} has {} quotes and grabbed {} quotes.",
+                stats.quotes, stats.grabbed
+            );
+            if let (Some(first), Some(last)) = (stats.first, stats.last) {
+                write!(
+                    buf,
+                    " First quoted on {}, last quoted on {}.",
+                    format_date(first),
+                    format_date(last)
+                )?;
+            }
+            return Ok(buf);
+        }
+        let stats = msg.db.quote_stats(Some(msg.quote_channels), STATS_LIMIT).await?;
+        if stats.quotes == 0 {
+            return Ok("There are no quotes yet.".into());
+        }
+        let mut buf = format!(
+            "{} quotes. Most quoted: {}.",
+            stats.quotes,
+            format_ranking(&stats.authors)
+        );
+        if !stats.grabbers.is_empty() {
+            write!(buf, " Top grabbers: {}.", format_ranking(&stats.grabbers))?;
         }
         Ok(buf)
     }
 }
+
+#[async_trait]
+impl Command for Search {
+    async fn execute(&self, msg: CommandContext) -> anyhow::Result<String> {
+        let Some(query) = msg.content else { return Ok("Invalid usage.".into()); };
+        let mut filter = parse_search(&query)?;
+        let channel = msg.quote_channels[0].clone();
+        filter.channels = Some(msg.quote_channels);
+        let results = msg
+            .db
+            .search_quotes(msg.author, channel, filter, self.limit)
+            .await?;
+        Ok(format_search_page(&results, 0, self.limit))
+    }
+}
+
+#[async_trait]
+impl Command for SearchPage {
+    async fn execute(&self, msg: CommandContext) -> anyhow::Result<String> {
+        let page = if let Some(page) = self.page {
+            page
+        } else {
+            let page = msg
+                .content
+                .and_then(|p| p.trim().parse::<usize>().ok())
+                .filter(|&p| p > 0)
+                .ok_or_else(|| UserError::new("Invalid page number."))?;
+            Page::Jump(page - 1)
+        };
+        let channel = msg.quote_channels[0].clone();
+        let Some((page, results)) = msg.db.search_page(msg.author, channel, page, self.limit).await? else {
+            return Ok("You need to initiate a search first using 'qsearch'.".into());
+        };
+        Ok(format_search_page(&results, page, self.limit))
+    }
+}
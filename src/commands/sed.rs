@@ -1,4 +1,7 @@
-use crate::bot::{Trigger, TriggerContext};
+use crate::{
+    bot::{Trigger, TriggerContext},
+    error::UserError,
+};
 use async_trait::async_trait;
 use regex::RegexBuilder;
 
@@ -27,7 +30,8 @@ impl Trigger for Sed {
 
             let re = RegexBuilder::new(find)
                 .case_insensitive(ignore_case)
-                .build()?;
+                .build()
+                .map_err(|_| UserError::new("Invalid regular expression."))?;
             let result = if global {
                 re.replace_all(&message, replace)
             } else {
@@ -40,7 +44,7 @@ impl Trigger for Sed {
                 ))
             } else {
                 ctx.history
-                    .edit_message(author, 0, result.to_string())
+                    .edit_message(&ctx.channel, author, 0, result.to_string())
                     .await;
                 Ok(format!("<{author}> {result}"))
             }
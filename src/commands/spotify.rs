@@ -1,11 +1,18 @@
-use crate::bot::{Trigger, TriggerContext};
+use crate::{
+    bot::{Trigger, TriggerContext},
+    error::UserError,
+};
 use async_trait::async_trait;
 use rspotify::{
     clients::BaseClient,
-    model::{enums::misc::Market, AlbumId, ArtistId, PlayableItem, PlaylistId, TrackId},
+    model::{enums::misc::Market, AlbumId, ArtistId, IdError, PlayableItem, PlaylistId, TrackId},
     ClientCredsSpotify, Credentials,
 };
 
+fn invalid_id(_: IdError) -> UserError {
+    UserError::new("\x037[Spotify]\x03 Error: Invalid resource ID")
+}
+
 pub struct Spotify {
     spotify: ClientCredsSpotify,
 }
@@ -58,14 +65,19 @@ async fn resolve_spotify(
     match resource_type {
         "track" => {
             let track = spotify
-                .track(TrackId::from_id(resource_id)?, Some(Market::FromToken))
+                .track(
+                    TrackId::from_id(resource_id).map_err(invalid_id)?,
+                    Some(Market::FromToken),
+                )
                 .await?;
-            let playtime = calculate_playtime(track.duration.num_seconds() as u64);
+            let playtime = calculate_playtime(track.duration.num_seconds().unsigned_abs());
             let artists: Vec<String> = track.artists.into_iter().map(|x| x.name).collect();
             Ok(format!("\x037[Spotify]\x03 Track: \x039\"{}\"\x03 - \x039\"{}\" \x0311|\x03 Album: \x039\"{}\" \x0311|\x03 Length:\x0315 {}:{:02} \x0311|", artists.join(", "), track.name, track.album.name, playtime.0, playtime.1))
         }
         "artist" => {
-            let artist = spotify.artist(ArtistId::from_id(resource_id)?).await?;
+            let artist = spotify
+                .artist(ArtistId::from_id(resource_id).map_err(invalid_id)?)
+                .await?;
             Ok(format!(
                 "\x037[Spotify]\x03 Artist: \x039\"{}\" \x0311|\x03 Genres:\x039 {} \x0311|",
                 artist.name,
@@ -74,31 +86,38 @@ async fn resolve_spotify(
         }
         "album" => {
             let album = spotify
-                .album(AlbumId::from_id(resource_id)?, Some(Market::FromToken))
+                .album(
+                    AlbumId::from_id(resource_id).map_err(invalid_id)?,
+                    Some(Market::FromToken),
+                )
                 .await?;
             let playtime = calculate_playtime(
                 album
                     .tracks
                     .items
                     .iter()
-                    .fold(0, |acc, x| acc + x.duration.num_seconds() as u64),
+                    .fold(0, |acc, x| acc + x.duration.num_seconds().unsigned_abs()),
             );
             Ok(format!("\x037[Spotify]\x03 Album: \x039\"{}\" \x0311|\x03 Tracks:\x0315 {} \x0311|\x03 Release date:\x039 {} \x0311|\x03 Length:\x0315 {}:{:02} \x0311|", album.name, album.tracks.total, album.release_date, playtime.0, playtime.1))
         }
         "playlist" => {
             let playlist = spotify
-                .playlist(PlaylistId::from_id(resource_id)?, None, None)
+                .playlist(
+                    PlaylistId::from_id(resource_id).map_err(invalid_id)?,
+                    None,
+                    None,
+                )
                 .await?;
             let mut tracks = 0;
             let playtime = calculate_playtime(playlist.tracks.items.iter().fold(0, |acc, x| {
                 x.track.as_ref().map_or(acc, |item| match item {
                     PlayableItem::Track(t) => {
                         tracks += 1;
-                        acc + t.duration.num_seconds() as u64
+                        acc + t.duration.num_seconds().unsigned_abs()
                     }
                     PlayableItem::Episode(e) => {
                         tracks += 1;
-                        acc + e.duration.num_seconds() as u64
+                        acc + e.duration.num_seconds().unsigned_abs()
                     }
                 })
             }));
@@ -2,12 +2,11 @@ use crate::bot::{Trigger, TriggerContext};
 use async_trait::async_trait;
 use fancy_regex::Regex;
 use htmlescape::decode_html;
-use hyper::header::HeaderValue;
-use reqwest::Client;
+use reqwest::{header::HeaderValue, Client};
 
 pub struct Title {
     http: Client,
-    title_regex: Regex,
+    regex: Regex,
     user_agent: String,
 }
 
@@ -15,7 +14,7 @@ impl Title {
     pub fn new(user_agent: Option<String>) -> anyhow::Result<Self> {
         Ok(Title {
             http: Client::new(),
-            title_regex: Regex::new(r"<title[^>]*>(.*?)</title>")?,
+            regex: Regex::new(r"<title[^>]*>(.*?)</title>")?,
             user_agent: user_agent.unwrap_or_else(|| {
                 format!("uberbot {} (reqwest)", env!("CARGO_PKG_VERSION")).to_string()
             }),
@@ -29,12 +28,15 @@ impl Trigger for Title {
         let url = ctx.captures.get(0).unwrap();
         tracing::debug!("url: {}", url);
 
-        let request = self
+        let mut request = self
             .http
             .get(url)
             .header("User-Agent", &self.user_agent)
-            .header("Accept", "text/html, */*")
-            .build()?;
+            .header("Accept", "text/html, */*");
+        if let Some(language) = &ctx.language {
+            request = request.header("Accept-Language", language);
+        }
+        let request = request.build()?;
         let response = self.http.execute(request).await?;
         let headers = response.headers();
 
@@ -45,7 +47,7 @@ impl Trigger for Title {
             .unwrap_or("text/html");
         if mime.contains("text/html") {
             let body = response.text().await?;
-            if let Some(tm) = self.title_regex.captures(&body)?.and_then(|c| c.get(1)) {
+            if let Some(tm) = self.regex.captures(&body)?.and_then(|c| c.get(1)) {
                 let title_match = tm.as_str();
                 let result = decode_html(title_match).unwrap_or_else(|_| title_match.to_string());
                 Ok(format!("\x039[Title]\x0311 {result}"))
@@ -1,23 +1,64 @@
-use std::{collections::HashMap, net::SocketAddr};
+use std::{
+    collections::{BTreeMap, HashMap},
+    env,
+    fmt::{self, Debug, Formatter},
+    fs,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+};
 
+use anyhow::Context;
 use serde::Deserialize;
 
-#[derive(Deserialize)]
+use crate::transport::split_target;
+
+/// Name of the network configured by the `[irc]` section
+pub const DEFAULT_NETWORK: &str = "default";
+
+/// A sensitive config value, redacted from debug output
+#[derive(Deserialize, Clone, PartialEq)]
+#[serde(transparent)]
+pub struct Secret(String);
+
+impl Secret {
+    pub fn new(secret: impl Into<String>) -> Self {
+        Self(secret.into())
+    }
+
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Debug for Secret {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str("[redacted]")
+    }
+}
+
+#[derive(Deserialize, Clone, PartialEq, Debug)]
 pub struct UberConfig {
     pub log_level: Option<String>,
-    pub irc: IrcConfig,
+    pub irc: Option<IrcConfig>,
+    #[serde(default)]
+    pub networks: BTreeMap<String, IrcConfig>,
     pub spotify: Option<SpotifyConfig>,
     pub bot: BotConfig,
     pub web: Option<HttpConfig>,
+    pub qotd: Option<QotdConfig>,
+    /// Per-channel settings, keyed by channel name, optionally qualified with a network name
+    #[serde(default)]
+    pub channels: HashMap<String, ChannelConfig>,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, Clone, PartialEq, Debug)]
 pub struct SpotifyConfig {
     pub client_id: String,
-    pub client_secret: String,
+    pub client_secret: Option<Secret>,
+    pub client_secret_file: Option<PathBuf>,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, Clone, PartialEq, Debug)]
 pub struct IrcConfig {
     pub channels: Vec<String>,
     pub host: String,
@@ -27,21 +68,302 @@ pub struct IrcConfig {
     pub alt_nicks: Option<Vec<String>>,
     pub port: u16,
     pub username: String,
-    pub sasl_pass: Option<String>,
+    pub sasl_pass: Option<Secret>,
+    pub sasl_pass_file: Option<PathBuf>,
+    pub prefixes: Option<Vec<String>>,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, Clone, PartialEq, Debug)]
 pub struct BotConfig {
     pub db_path: Option<String>,
     pub history_depth: usize,
     pub search_limit: Option<usize>,
+    #[serde(default)]
     pub prefixes: Vec<String>,
     pub ignored_triggers: Option<HashMap<String, Vec<String>>>,
     pub user_agent: Option<String>,
+    pub admin_channel: Option<String>,
+    pub report_cooldown: Option<u64>,
+    /// Nicks of users who can manage all quotes
+    #[serde(default)]
+    pub admins: Vec<String>,
+    /// How long after grabbing a quote it can be removed with `ungrab`, in seconds
+    pub ungrab_window: Option<u64>,
+    /// Whether `quot` picks quotes with a higher score more often
+    #[serde(default)]
+    pub weighted_quotes: bool,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, Clone, PartialEq, Debug)]
 pub struct HttpConfig {
     pub listen: SocketAddr,
     pub webhooks: HashMap<String, String>,
 }
+
+/// Daily quote of the day announcements
+#[derive(Deserialize, Clone, PartialEq, Debug)]
+pub struct QotdConfig {
+    /// Channels the quote of the day is posted to, optionally qualified with a network name
+    pub channels: Vec<String>,
+    /// Time of the announcement, `HH:MM` in UTC
+    pub time: String,
+    /// How many days have to pass before a quote is posted in the same channel again
+    pub repeat_window: Option<u64>,
+}
+
+impl QotdConfig {
+    /// Returns the time of the announcement in seconds since midnight
+    pub fn time_of_day(&self) -> anyhow::Result<u64> {
+        let parsed = self.time.split_once(':').and_then(|(h, m)| {
+            let (h, m): (u64, u64) = (h.parse().ok()?, m.parse().ok()?);
+            (h < 24 && m < 60).then_some(h * 3600 + m * 60)
+        });
+        parsed.with_context(|| format!("Invalid qotd.time '{}', expected HH:MM", self.time))
+    }
+}
+
+/// Settings of a single channel, unset options fall back to the global settings
+#[derive(Deserialize, Clone, PartialEq, Debug, Default)]
+pub struct ChannelConfig {
+    /// Key (password) used to join the channel
+    pub key: Option<Secret>,
+    pub key_file: Option<PathBuf>,
+    pub prefixes: Option<Vec<String>>,
+    /// If set, only these commands can be used in the channel
+    pub commands: Option<Vec<String>>,
+    #[serde(default)]
+    pub disabled_commands: Vec<String>,
+    /// If set, only these triggers are active in the channel
+    pub triggers: Option<Vec<String>>,
+    #[serde(default)]
+    pub disabled_triggers: Vec<String>,
+    /// Whether replies can contain IRC formatting (colors, bold, ...)
+    pub color: Option<bool>,
+    pub rate_limit: Option<RateLimit>,
+    /// Preferred language of the channel, e.g. `de` or `en-US`
+    pub language: Option<String>,
+    /// Channels with the same quote namespace share their quotes,
+    /// by default only quotes grabbed in the channel itself are shown
+    pub quote_namespace: Option<String>,
+}
+
+/// Limits how many commands and triggers the bot answers in a channel
+#[derive(Deserialize, Clone, PartialEq, Debug)]
+pub struct RateLimit {
+    pub messages: usize,
+    /// Length of the period in seconds
+    pub period: u64,
+}
+
+impl IrcConfig {
+    /// Checks if the settings used for connecting to the network are the same
+    pub fn same_connection(&self, other: &IrcConfig) -> bool {
+        self.host == other.host
+            && self.port == other.port
+            && self.tls == other.tls
+            && self.mode == other.mode
+            && self.nickname == other.nickname
+            && self.alt_nicks == other.alt_nicks
+            && self.username == other.username
+            && self.sasl_pass == other.sasl_pass
+    }
+}
+
+impl UberConfig {
+    /// Reads and parses the config file, then resolves its secrets
+    pub fn load(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let config_str = fs::read_to_string(path)?;
+        let mut cfg: Self = toml::from_str(&config_str)?;
+        cfg.resolve_secrets()?;
+        Ok(cfg)
+    }
+
+    /// Fills in secrets from environment variables and `*_file` options.
+    ///
+    /// Every secret can be overridden with an environment variable named after its path
+    /// in the config, e.g. `UBERBOT_IRC_SASL_PASS`, `UBERBOT_NETWORKS_LIBERA_SASL_PASS`
+    /// or `UBERBOT_SPOTIFY_CLIENT_SECRET`.
+    pub fn resolve_secrets(&mut self) -> anyhow::Result<()> {
+        if let Some(irc) = &mut self.irc {
+            resolve_secret(
+                &mut irc.sasl_pass,
+                irc.sasl_pass_file.as_ref(),
+                &["irc", "sasl_pass"],
+            )?;
+        }
+        for (name, network) in &mut self.networks {
+            resolve_secret(
+                &mut network.sasl_pass,
+                network.sasl_pass_file.as_ref(),
+                &["networks", name, "sasl_pass"],
+            )?;
+        }
+        for (target, channel) in &mut self.channels {
+            resolve_secret(
+                &mut channel.key,
+                channel.key_file.as_ref(),
+                &["channels", target, "key"],
+            )?;
+        }
+        if let Some(spotify) = &mut self.spotify {
+            resolve_secret(
+                &mut spotify.client_secret,
+                spotify.client_secret_file.as_ref(),
+                &["spotify", "client_secret"],
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Returns all configured networks and their names.
+    /// The network configured by the `[irc]` section is named [`DEFAULT_NETWORK`].
+    pub fn networks(&self) -> Vec<(&str, &IrcConfig)> {
+        if let Some(irc) = &self.irc {
+            vec![(DEFAULT_NETWORK, irc)]
+        } else {
+            self.networks.iter().map(|(k, v)| (k.as_str(), v)).collect()
+        }
+    }
+
+    /// Returns the path of the quote database
+    pub fn db_path(&self) -> &str {
+        self.bot.db_path.as_deref().unwrap_or("uberbot.db3")
+    }
+
+    /// Returns the command prefixes used on a network
+    pub fn prefixes(&self, network: &IrcConfig) -> Vec<String> {
+        network
+            .prefixes
+            .clone()
+            .unwrap_or_else(|| self.bot.prefixes.clone())
+    }
+
+    /// Returns the settings of the channels on a network, keyed by the lowercase channel name.
+    /// Settings qualified with the network name replace the unqualified ones.
+    pub fn channel_configs(&self, network: &str) -> HashMap<String, ChannelConfig> {
+        let (mut channels, mut qualified) = (HashMap::new(), HashMap::new());
+        for (target, cfg) in &self.channels {
+            let (target_network, channel) = split_target(target);
+            let channel = channel.to_ascii_lowercase();
+            match target_network {
+                None => channels.insert(channel, cfg.clone()),
+                Some(n) if n == network => qualified.insert(channel, cfg.clone()),
+                Some(_) => None,
+            };
+        }
+        channels.extend(qualified);
+        channels
+    }
+
+    /// Returns the keys of the channels on a network
+    pub fn channel_keys(&self, network: &str) -> HashMap<String, String> {
+        self.channel_configs(network)
+            .into_iter()
+            .filter_map(|(channel, cfg)| Some((channel, cfg.key?.expose().to_string())))
+            .collect()
+    }
+
+    /// Checks the config for errors that can't be caught during deserialization
+    pub fn validate(&self) -> anyhow::Result<()> {
+        if self.irc.is_some() && !self.networks.is_empty() {
+            anyhow::bail!("Use either the [irc] section or [networks.<name>] sections, not both");
+        }
+        let networks = self.networks();
+        if networks.is_empty() {
+            anyhow::bail!("You have to configure at least one network");
+        }
+        for (name, network) in &networks {
+            if name.is_empty() || name.contains(['/', '#', '&']) {
+                anyhow::bail!("Invalid network name '{name}'");
+            }
+            if self.prefixes(network).is_empty() {
+                anyhow::bail!("You have to specify at least one prefix (network '{name}')");
+            }
+        }
+        let check_target = |target: &str, what: &str| {
+            match split_target(target).0 {
+                Some(network) if !networks.iter().any(|(n, _)| *n == network) => {
+                    anyhow::bail!("{what} '{target}' refers to an unknown network '{network}'")
+                }
+                None if networks.len() > 1 => anyhow::bail!(
+                    "{what} '{target}' has to be qualified with a network name (e.g. 'network/#channel')"
+                ),
+                _ => Ok(()),
+            }
+        };
+        if let Some(admin_channel) = &self.bot.admin_channel {
+            // unqualified admin channels are allowed, reports are sent to the network the error occurred on
+            if split_target(admin_channel).0.is_some() {
+                check_target(admin_channel, "Admin channel")?;
+            }
+        }
+        for (target, channel) in &self.channels {
+            if !split_target(target).1.starts_with(['#', '&']) {
+                anyhow::bail!("Invalid channel name '{target}' in [channels]");
+            }
+            if split_target(target).0.is_some() {
+                check_target(target, "Channel")?;
+            }
+            if matches!(&channel.prefixes, Some(p) if p.is_empty()) {
+                anyhow::bail!("You have to specify at least one prefix (channel '{target}')");
+            }
+            if let Some(limit) = &channel.rate_limit {
+                if limit.messages == 0 || limit.period == 0 {
+                    anyhow::bail!(
+                        "The rate limit of channel '{target}' has to be greater than zero"
+                    );
+                }
+            }
+        }
+        if let Some(spotify) = &self.spotify {
+            if !cfg!(feature = "spotify") {
+                anyhow::bail!(
+                    "The [spotify] section is present, but überbot was compiled without the 'spotify' feature"
+                );
+            }
+            if spotify.client_secret.is_none() {
+                anyhow::bail!("Set either spotify.client_secret or spotify.client_secret_file");
+            }
+        }
+        if let Some(web) = &self.web {
+            if !cfg!(feature = "web") {
+                anyhow::bail!(
+                    "The [web] section is present, but überbot was compiled without the 'web' feature"
+                );
+            }
+            for target in web.webhooks.values() {
+                check_target(target, "Webhook target")?;
+            }
+        }
+        if let Some(qotd) = &self.qotd {
+            qotd.time_of_day()?;
+            for target in &qotd.channels {
+                check_target(target, "Quote of the day channel")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Resolves a secret from the environment, its file or the inline value, in that order
+fn resolve_secret(
+    secret: &mut Option<Secret>,
+    file: Option<&PathBuf>,
+    path: &[&str],
+) -> anyhow::Result<()> {
+    let name = path.join(".");
+    let var = format!("UBERBOT_{}", path.join("_"))
+        .to_ascii_uppercase()
+        .replace(|c: char| !c.is_ascii_alphanumeric(), "_");
+    if let Ok(value) = env::var(&var) {
+        *secret = Some(Secret(value));
+    } else if let Some(file) = file {
+        if secret.is_some() {
+            anyhow::bail!("{name} and {name}_file can't be used together");
+        }
+        let value = fs::read_to_string(file)
+            .with_context(|| format!("Couldn't read {name}_file '{}'", file.display()))?;
+        *secret = Some(Secret(value.trim_end_matches(['\r', '\n']).to_string()));
+    }
+    Ok(())
+}
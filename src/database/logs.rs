@@ -0,0 +1,244 @@
+//! Parsing irssi, weechat and ZNC logs, used for importing old messages as quotes
+use super::{Quote, QuoteLine};
+use std::{io::BufRead, path::Path};
+
+/// Message read from a log
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LogMessage {
+    /// Unix timestamp of the message, `None` if the log didn't contain its date
+    pub timestamp: Option<i64>,
+    pub nick: String,
+    pub text: String,
+}
+
+enum LogLine {
+    Message(LogMessage),
+    /// Day changed, the timestamp of its midnight
+    Date(i64),
+    /// Joins, parts, actions and everything else that isn't a message
+    Other,
+}
+
+/// Reads messages line by line, keeping track of the current day.
+/// Times are assumed to be in UTC.
+#[derive(Default)]
+pub struct LogParser {
+    date: Option<i64>,
+}
+
+/// Quotes read from a log, and the number of lines that weren't messages
+pub struct LogQuotes {
+    pub quotes: Vec<Quote>,
+    pub skipped: usize,
+}
+
+const MONTHS: [&str; 12] = [
+    "jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec",
+];
+
+impl LogParser {
+    /// `date` is the midnight of the first day in the log, see [`date_from_path`]
+    pub fn new(date: Option<i64>) -> Self {
+        Self { date }
+    }
+
+    /// Parses a line of any supported format, returns `None` if it isn't a message
+    pub fn parse_line(&mut self, line: &str) -> Option<LogMessage> {
+        match self.parse(line) {
+            LogLine::Message(msg) => Some(msg),
+            _ => None,
+        }
+    }
+
+    fn parse(&mut self, line: &str) -> LogLine {
+        let line = line.trim_end_matches(['\r', '\n']);
+        let parsed = if let Some(rest) = line.strip_prefix("--- ") {
+            // irssi: --- Log opened Mon Jan 02 12:00:00 2023, --- Day changed Tue Jan 03 2023
+            irssi_date(rest).map_or(LogLine::Other, LogLine::Date)
+        } else if let Some(rest) = line.strip_prefix('[') {
+            // ZNC: [12:34:56] <nick> text
+            rest.split_once("] ")
+                .and_then(|(time, msg)| self.message(parse_time(time)?, msg))
+                .map_or(LogLine::Other, LogLine::Message)
+        } else if line.contains('\t') {
+            // weechat: 2023-01-02 12:34:56\t@nick\ttext
+            weechat_message(line).map_or(LogLine::Other, LogLine::Message)
+        } else {
+            // irssi: 12:34 <@nick> text
+            line.split_once(' ')
+                .and_then(|(time, msg)| self.message(parse_time(time)?, msg))
+                .map_or(LogLine::Other, LogLine::Message)
+        };
+        if let LogLine::Date(date) = parsed {
+            self.date = Some(date);
+        }
+        parsed
+    }
+
+    /// Parses `<nick> text`, `time` is the number of seconds since midnight
+    fn message(&self, time: i64, msg: &str) -> Option<LogMessage> {
+        let (nick, text) = msg.strip_prefix('<')?.split_once('>')?;
+        let nick = strip_mode(nick.trim());
+        let text = text.strip_prefix(' ').unwrap_or(text);
+        if nick.is_empty() || text.trim().is_empty() {
+            return None;
+        }
+        Some(LogMessage {
+            timestamp: self.date.map(|date| date + time),
+            nick: nick.into(),
+            text: text.into(),
+        })
+    }
+}
+
+/// Reads quotes from a curated log, the quotes are separated by empty lines.
+/// Lines of a single user are joined like `grab`, lines of several users become a conversation.
+pub fn read_log_quotes(
+    reader: impl BufRead,
+    date: Option<i64>,
+    channel: Option<&str>,
+) -> anyhow::Result<LogQuotes> {
+    let mut parser = LogParser::new(date);
+    let mut quotes = Vec::new();
+    let mut messages = Vec::new();
+    let mut skipped = 0;
+    for line in reader.lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            quotes.extend(quote_from_messages(&mut messages, channel));
+            continue;
+        }
+        match parser.parse(&line) {
+            LogLine::Message(msg) => messages.push(msg),
+            LogLine::Date(_) => {}
+            LogLine::Other => skipped += 1,
+        }
+    }
+    quotes.extend(quote_from_messages(&mut messages, channel));
+    Ok(LogQuotes { quotes, skipped })
+}
+
+fn quote_from_messages(messages: &mut Vec<LogMessage>, channel: Option<&str>) -> Option<Quote> {
+    let first = messages.first()?;
+    let timestamp = first.timestamp;
+    let single = messages
+        .iter()
+        .all(|m| m.nick.eq_ignore_ascii_case(&first.nick));
+    let quote = if single {
+        Quote {
+            author: first.nick.clone(),
+            quote: messages
+                .iter()
+                .map(|m| m.text.as_str())
+                .collect::<Vec<_>>()
+                .join(" | "),
+            ..Quote::default()
+        }
+    } else {
+        Quote::conversation(
+            messages
+                .iter()
+                .map(|m| QuoteLine {
+                    speaker: m.nick.clone(),
+                    message: m.text.clone(),
+                })
+                .collect(),
+        )
+    };
+    messages.clear();
+    Some(Quote {
+        channel: channel.map(str::to_lowercase),
+        timestamp,
+        ..quote
+    })
+}
+
+/// Finds a `YYYY-MM-DD` or `YYYYMMDD` date in the file name, like the ones ZNC and irssi use
+pub fn date_from_path(path: &Path) -> Option<i64> {
+    let name = path.file_stem()?.to_str()?;
+    name.char_indices().find_map(|(i, _)| {
+        let rest = &name[i..];
+        rest.get(..10).and_then(parse_date).or_else(|| {
+            let compact = rest
+                .get(..8)
+                .filter(|d| d.bytes().all(|b| b.is_ascii_digit()))?;
+            parse_date(&format!(
+                "{}-{}-{}",
+                &compact[..4],
+                &compact[4..6],
+                &compact[6..]
+            ))
+        })
+    })
+}
+
+fn weechat_message(line: &str) -> Option<LogMessage> {
+    let mut parts = line.splitn(3, '\t');
+    let (datetime, nick, text) = (parts.next()?, parts.next()?, parts.next()?);
+    let (date, time) = datetime.split_once(' ')?;
+    let nick = strip_mode(nick.trim());
+    // joins, parts and actions are prefixed with arrows, dashes or an asterisk
+    if nick.is_empty() || !nick.chars().any(char::is_alphanumeric) || text.trim().is_empty() {
+        return None;
+    }
+    Some(LogMessage {
+        timestamp: Some(parse_date(date)? + parse_time(time)?),
+        nick: nick.into(),
+        text: text.into(),
+    })
+}
+
+fn irssi_date(line: &str) -> Option<i64> {
+    let rest = line
+        .strip_prefix("Log opened ")
+        .or_else(|| line.strip_prefix("Day changed "))?;
+    let parts: Vec<&str> = rest.split_whitespace().collect();
+    let [_, month, day, .., year] = parts.as_slice() else {
+        return None;
+    };
+    let (_, month) = MONTHS
+        .iter()
+        .zip(1..)
+        .find(|(m, _)| month.eq_ignore_ascii_case(m))?;
+    days_from_civil(year.parse().ok()?, month, day.parse().ok()?)
+}
+
+/// Removes the channel mode prefix of a nick, e.g. `@` for operators
+fn strip_mode(nick: &str) -> &str {
+    nick.trim_start_matches(['@', '+', '%', '~', '&', '!'])
+}
+
+/// Parses `HH:MM` or `HH:MM:SS` as seconds since midnight
+fn parse_time(time: &str) -> Option<i64> {
+    let mut parts = time.split(':');
+    let hours: i64 = parts.next()?.parse().ok()?;
+    let minutes: i64 = parts.next()?.parse().ok()?;
+    let seconds: i64 = parts.next().map_or(Some(0), |s| s.parse().ok())?;
+    if parts.next().is_some() || hours > 23 || minutes > 59 || seconds > 60 {
+        return None;
+    }
+    Some(hours * 3600 + minutes * 60 + seconds)
+}
+
+/// Parses `YYYY-MM-DD` as the timestamp of its midnight
+fn parse_date(date: &str) -> Option<i64> {
+    let mut parts = date.splitn(3, '-');
+    let year = parts.next().filter(|y| y.len() == 4)?.parse().ok()?;
+    let month = parts.next().filter(|m| m.len() == 2)?.parse().ok()?;
+    let day = parts.next().filter(|d| d.len() == 2)?.parse().ok()?;
+    days_from_civil(year, month, day)
+}
+
+fn days_from_civil(year: i64, month: i64, day: i64) -> Option<i64> {
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return None;
+    }
+    // http://howardhinnant.github.io/date_algorithms.html#days_from_civil
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let yoe = year - era * 400;
+    let mp = (month + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    Some((era * 146_097 + doe - 719_468) * 86400)
+}
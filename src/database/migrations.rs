@@ -0,0 +1,127 @@
+use rusqlite::Connection;
+
+/// Schema migrations, applied in order.
+/// The number of applied migrations is stored in the `user_version` pragma.
+const MIGRATIONS: &[&str] = &[
+    // 1: move quotes from the FTS table to a regular table with metadata,
+    // indexed by an external content FTS table
+    "
+    create virtual table if not exists quotes using fts5(username, quote);
+    alter table quotes rename to quotes_v0;
+    create table quotes (
+        id integer primary key,
+        author text not null,
+        quote text not null,
+        channel text,
+        grabbed_by text,
+        timestamp integer
+    );
+    insert into quotes(id, author, quote) select rowid, username, quote from quotes_v0 order by rowid;
+    drop table quotes_v0;
+    create index quotes_author on quotes(author);
+    create virtual table quotes_fts using fts5(author, quote, content='quotes', content_rowid='id');
+    insert into quotes_fts(quotes_fts) values('rebuild');
+    create trigger quotes_ai after insert on quotes begin
+        insert into quotes_fts(rowid, author, quote) values (new.id, new.author, new.quote);
+    end;
+    create trigger quotes_ad after delete on quotes begin
+        insert into quotes_fts(quotes_fts, rowid, author, quote) values ('delete', old.id, old.author, old.quote);
+    end;
+    create trigger quotes_au after update on quotes begin
+        insert into quotes_fts(quotes_fts, rowid, author, quote) values ('delete', old.id, old.author, old.quote);
+        insert into quotes_fts(rowid, author, quote) values (new.id, new.author, new.quote);
+    end;
+    ",
+    // 2: votes, the score of each quote is kept up to date by triggers
+    "
+    alter table quotes add column score integer not null default 0;
+    create table votes (
+        quote_id integer not null,
+        voter text not null collate nocase,
+        vote integer not null check (vote in (-1, 1)),
+        primary key (quote_id, voter)
+    );
+    create trigger votes_ai after insert on votes begin
+        update quotes set score = score + new.vote where id = new.quote_id;
+    end;
+    create trigger votes_au after update on votes begin
+        update quotes set score = score - old.vote + new.vote where id = new.quote_id;
+    end;
+    create trigger quotes_ad_votes after delete on quotes begin
+        delete from votes where quote_id = old.id;
+    end;
+    create index quotes_score on quotes(score);
+    -- score changes don't have to be indexed
+    drop trigger quotes_au;
+    create trigger quotes_au after update of author, quote on quotes begin
+        insert into quotes_fts(quotes_fts, rowid, author, quote) values ('delete', old.id, old.author, old.quote);
+        insert into quotes_fts(rowid, author, quote) values (new.id, new.author, new.quote);
+    end;
+    ",
+    // 3: conversations, quotes with a speaker for each line
+    "
+    create table quote_lines (
+        quote_id integer not null,
+        line integer not null,
+        speaker text not null collate nocase,
+        message text not null,
+        primary key (quote_id, line)
+    );
+    create index quote_lines_speaker on quote_lines(speaker);
+    create trigger quotes_ad_lines after delete on quotes begin
+        delete from quote_lines where quote_id = old.id;
+    end;
+    ",
+    // 4: users who don't want to be quoted, removing a vote updates the score
+    "
+    create table opt_outs (
+        nick text primary key collate nocase,
+        timestamp integer not null default (strftime('%s','now'))
+    );
+    create trigger votes_ad after delete on votes begin
+        update quotes set score = score - old.vote where id = old.quote_id;
+    end;
+    ",
+    // 5: quotes of the day posted in each channel
+    "
+    create table qotd (
+        channel text not null collate nocase,
+        quote_id integer not null,
+        timestamp integer not null default (strftime('%s','now'))
+    );
+    create index qotd_channel on qotd(channel, timestamp);
+    create trigger quotes_ad_qotd after delete on quotes begin
+        delete from qotd where quote_id = old.id;
+    end;
+    ",
+];
+
+/// Returns the schema version of the database
+pub fn version(db: &Connection) -> rusqlite::Result<usize> {
+    db.query_row("pragma user_version", [], |row| row.get(0))
+}
+
+/// Returns the number of migrations that haven't been applied yet
+pub fn pending(db: &Connection) -> anyhow::Result<usize> {
+    let version = version(db)?;
+    if version > MIGRATIONS.len() {
+        anyhow::bail!(
+            "The database schema (version {version}) is newer than this version of überbot supports ({})",
+            MIGRATIONS.len()
+        );
+    }
+    Ok(MIGRATIONS.len() - version)
+}
+
+/// Brings the database schema up to date, each migration runs in its own transaction
+pub fn migrate(db: &mut Connection) -> anyhow::Result<()> {
+    pending(db)?;
+    for (i, sql) in MIGRATIONS.iter().enumerate().skip(version(db)?) {
+        tracing::info!("Migrating the database to version {}", i + 1);
+        let tx = db.transaction()?;
+        tx.execute_batch(sql)?;
+        tx.pragma_update(None, "user_version", i + 1)?;
+        tx.commit()?;
+    }
+    Ok(())
+}
@@ -0,0 +1,885 @@
+use rand::distributions::{Distribution, WeightedIndex};
+use rusqlite::{params, OptionalExtension, Params, Row};
+use rusqlite::{Connection, OpenFlags};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    path::Path,
+    time::{SystemTime, UNIX_EPOCH},
+};
+use tokio::{
+    sync::{
+        mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender},
+        oneshot,
+    },
+    time::Instant,
+};
+
+pub mod logs;
+pub mod migrations;
+pub mod transfer;
+
+pub struct ExecutorConnection(UnboundedSender<Task>);
+impl Clone for ExecutorConnection {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+macro_rules! executor_wrapper {
+    ($($task:ident / $fn:ident, ($($arg:ident: $ty:ty),*) => $ret:ty)*) => {
+        #[derive(Debug)]
+        enum Task {
+            $($task{tx:oneshot::Sender<$ret>,$($arg:$ty,)*}),*
+        }
+
+        impl ExecutorConnection {
+            $(pub async fn $fn(&self, $($arg: $ty),*) -> $ret {
+                let (tx, rx) = oneshot::channel();
+                self.0.send(Task::$task{tx,$($arg),*}).unwrap();
+                rx.await.unwrap()
+            })*
+        }
+    };
+}
+
+executor_wrapper! {
+    AddQuote / add_quote, (quote: Quote, channels: Option<Vec<String>>) => rusqlite::Result<AddedQuote>
+    GetQuote / get_quote, (filter: QuoteFilter, weighted: bool) => rusqlite::Result<Option<Quote>>
+    GetQuoteById / get_quote_by_id, (id: i64) => rusqlite::Result<Option<Quote>>
+    DeleteQuote / delete_quote, (id: i64) => rusqlite::Result<bool>
+    UndoGrab / undo_grab, (grabbed_by: String, channel: String, max_age: u64) => rusqlite::Result<Option<Quote>>
+    Vote / vote, (quote_id: i64, voter: String, vote: i64) => rusqlite::Result<Option<i64>>
+    TopQuotes / top_quotes, (filter: QuoteFilter, limit: usize) => rusqlite::Result<Vec<Quote>>
+    QuoteStats / quote_stats, (channels: Option<Vec<String>>, limit: usize) => rusqlite::Result<QuoteStats>
+    UserStats / user_stats, (user: String, channels: Option<Vec<String>>) => rusqlite::Result<UserStats>
+    SetOptOut / set_opt_out, (nick: String, opted_out: bool) => rusqlite::Result<bool>
+    OptedOut / opted_out, () => rusqlite::Result<Vec<String>>
+    ForgetUser / forget_user, (nick: String) => rusqlite::Result<usize>
+    StartSearch / search_quotes, (user: String, channel: String, filter: QuoteFilter, limit: usize) => rusqlite::Result<Vec<SearchResult>>
+    SearchPage / search_page, (user: String, channel: String, page: Page, limit: usize) => rusqlite::Result<Option<(usize, Vec<SearchResult>)>>
+    QuoteOfTheDay / quote_of_the_day, (channel: String, channels: Vec<String>, since: i64, repeat_window: u64) => rusqlite::Result<Option<Quote>>
+}
+
+pub struct DbExecutor {
+    rx: UnboundedReceiver<Task>,
+    db: rusqlite::Connection,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct Quote {
+    /// Id of the quote, `None` if it wasn't saved yet
+    pub id: Option<i64>,
+    pub author: String,
+    pub quote: String,
+    /// Channel the quote was grabbed in
+    pub channel: Option<String>,
+    /// Nick of the user who grabbed the quote
+    pub grabbed_by: Option<String>,
+    /// Unix timestamp of when the quote was grabbed, the current time is used if it's not set
+    pub timestamp: Option<i64>,
+    /// Sum of all votes
+    #[serde(default)]
+    pub score: i64,
+    /// Lines of a conversation, empty if the quote has a single author
+    #[serde(default)]
+    pub lines: Vec<QuoteLine>,
+}
+
+/// Line of a conversation quote
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct QuoteLine {
+    pub speaker: String,
+    pub message: String,
+}
+
+/// Restricts which quotes are returned by [`ExecutorConnection::get_quote`],
+/// [`ExecutorConnection::top_quotes`] and [`ExecutorConnection::search_quotes`]
+#[derive(Debug, Default)]
+pub struct QuoteFilter {
+    pub author: Option<String>,
+    /// Full-text search query the quote has to match
+    pub query: Option<String>,
+    /// Channels the quote was grabbed in. Quotes without a channel,
+    /// which were added before channels were recorded, are in every channel.
+    pub channels: Option<Vec<String>>,
+    /// Only quotes grabbed after this day (`YYYY-MM-DD`, UTC)
+    pub after: Option<String>,
+    /// Only quotes grabbed before this day (`YYYY-MM-DD`, UTC)
+    pub before: Option<String>,
+}
+
+impl QuoteFilter {
+    fn channel_list(&self) -> Option<String> {
+        self.channels.as_deref().map(channel_list)
+    }
+}
+
+/// Returns the channels as a comma-separated list, which is bound to queries.
+/// Commas can't be used in channel names.
+fn channel_list(channels: &[String]) -> String {
+    format!(",{},", channels.join(",")).to_ascii_lowercase()
+}
+
+/// Totals of the quotes of some channels, or of the whole quote database
+#[derive(Debug)]
+pub struct QuoteStats {
+    pub quotes: u64,
+    /// Most quoted users and their number of quotes, including conversations they took part in
+    pub authors: Vec<(String, u64)>,
+    /// Users who grabbed the most quotes and how many they grabbed
+    pub grabbers: Vec<(String, u64)>,
+}
+
+/// Quote statistics of a single user
+#[derive(Debug)]
+pub struct UserStats {
+    /// Number of quotes by the user, including conversations they took part in
+    pub quotes: u64,
+    /// Number of quotes grabbed by the user
+    pub grabbed: u64,
+    /// Unix timestamps of the user's first and last quotes, if they're known
+    pub first: Option<i64>,
+    pub last: Option<i64>,
+}
+
+/// Quote found by a search
+#[derive(Debug)]
+pub struct SearchResult {
+    pub quote: Quote,
+    /// Part of the quote matching the query, with the matches in bold
+    pub snippet: String,
+}
+
+/// Page of search results requested with [`ExecutorConnection::search_page`]
+#[derive(Debug, Clone, Copy)]
+pub enum Page {
+    Next,
+    Previous,
+    /// Page number, starting from 0
+    Jump(usize),
+}
+
+/// Search of a user in a channel, which can be paged through until it expires
+struct SearchSession {
+    filter: QuoteFilter,
+    page: usize,
+    last_used: Instant,
+}
+
+/// Searches that haven't been used for this many seconds are forgotten
+const SEARCH_EXPIRY: u64 = 1800;
+
+/// Result of adding a quote
+#[derive(Debug, PartialEq, Eq)]
+pub enum AddedQuote {
+    /// The quote was saved with this id
+    Added(i64),
+    /// The quote wasn't saved, because it duplicates the quote with this id
+    Duplicate(i64),
+}
+
+/// Quotes grabbed within this many seconds of each other are duplicates if they share a message
+const DUPLICATE_WINDOW: i64 = 3600;
+
+/// Lowercases the text and collapses whitespace, for comparing quotes
+fn normalize(text: &str) -> String {
+    text.split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase()
+}
+
+/// Returns the current time as a Unix timestamp
+pub(crate) fn unix_now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| i64::try_from(d.as_secs()).unwrap_or(i64::MAX))
+}
+
+/// Applies a [`QuoteFilter`] except for its query, with its author, channel, after and before
+/// bound to `?1`, `?3`, `?4` and `?5`.
+/// Quotes match an author if they were said by them or if they took part in the conversation.
+const FILTER: &str = "(?1 is null or (quotes.author = ?1 or quotes.id in (select quote_id from quote_lines where speaker = ?1))) and (?3 is null or quotes.channel is null or instr(?3, ',' || lower(quotes.channel) || ',') > 0) and (?4 is null or quotes.timestamp >= strftime('%s', ?4, '+1 day')) and (?5 is null or quotes.timestamp < strftime('%s', ?5))";
+
+/// Applies the query of a [`QuoteFilter`], bound to `?2`
+const MATCHES_QUERY: &str = "(?2 is null or quotes.id in (select rowid from quotes_fts where quotes_fts.quote match ?2))";
+
+/// Columns selected by queries returning quotes, in the order expected by [`Quote::from_row`]
+const QUOTE_COLUMNS: &str = "quotes.id, quotes.author, quotes.quote, quotes.channel, quotes.grabbed_by, quotes.timestamp, quotes.score";
+
+impl Quote {
+    /// Reads a quote selected with [`QUOTE_COLUMNS`]
+    fn from_row(row: &Row) -> rusqlite::Result<Self> {
+        Ok(Self {
+            id: row.get(0)?,
+            author: row.get(1)?,
+            quote: row.get(2)?,
+            channel: row.get(3)?,
+            grabbed_by: row.get(4)?,
+            timestamp: row.get(5)?,
+            score: row.get(6)?,
+            lines: Vec::new(),
+        })
+    }
+
+    /// Creates a conversation quote, rendered as `<alice> ... <bob> ...`.
+    /// The speakers are stored as its author, in the order they first spoke.
+    pub fn conversation(lines: Vec<QuoteLine>) -> Self {
+        let mut speakers: Vec<&str> = Vec::new();
+        for line in &lines {
+            if !speakers.iter().any(|s| s.eq_ignore_ascii_case(&line.speaker)) {
+                speakers.push(&line.speaker);
+            }
+        }
+        let quote = lines
+            .iter()
+            .map(|l| format!("<{}> {}", l.speaker, l.message))
+            .collect::<Vec<_>>()
+            .join(" ");
+        Self {
+            author: speakers.join(", "),
+            quote,
+            lines,
+            ..Self::default()
+        }
+    }
+}
+
+impl DbExecutor {
+    pub fn create(dbpath: &str) -> anyhow::Result<(Self, ExecutorConnection)> {
+        let (tx, rx) = unbounded_channel();
+        let mut db = Connection::open(dbpath)?;
+        migrations::migrate(&mut db)?;
+        tracing::debug!("Database connected ({})", dbpath);
+        Ok((Self { rx, db }, ExecutorConnection(tx)))
+    }
+
+    pub fn run(mut self) {
+        // searches are kept per user and channel, so results from other channels aren't shown
+        let mut searches: HashMap<(String, String), SearchSession> = HashMap::new();
+        while let Some(task) = self.rx.blocking_recv() {
+            let before = Instant::now();
+            tracing::debug!("got task {:?}", task);
+            match task {
+                Task::AddQuote {
+                    tx,
+                    mut quote,
+                    channels,
+                } => {
+                    quote.author.make_ascii_lowercase();
+                    let _e = tx.send(self.add_quote(&quote, channels.as_deref()));
+                }
+                Task::GetQuote {
+                    tx,
+                    mut filter,
+                    weighted,
+                } => {
+                    filter.author = filter.author.map(|a| a.to_ascii_lowercase());
+                    let result = if weighted {
+                        self.weighted_random_quote(&filter)
+                    } else {
+                        self.query_quote(&format!("select {QUOTE_COLUMNS} from quotes where {FILTER} and {MATCHES_QUERY} order by random() limit 1"), params![filter.author, filter.query, filter.channel_list(), filter.after, filter.before])
+                    };
+                    let _e = tx.send(result);
+                }
+                Task::GetQuoteById { tx, id } => {
+                    let result = self.query_quote(
+                        &format!("select {QUOTE_COLUMNS} from quotes where id = ?"),
+                        params![id],
+                    );
+                    let _e = tx.send(result);
+                }
+                Task::DeleteQuote { tx, id } => {
+                    let result = self
+                        .db
+                        .execute("delete from quotes where id = ?", params![id])
+                        .map(|n| n > 0);
+                    let _e = tx.send(result);
+                }
+                Task::UndoGrab {
+                    tx,
+                    grabbed_by,
+                    channel,
+                    max_age,
+                } => {
+                    let _e = tx.send(self.undo_grab(&grabbed_by, &channel, max_age));
+                }
+                Task::Vote {
+                    tx,
+                    quote_id,
+                    voter,
+                    vote,
+                } => {
+                    let _e = tx.send(self.vote(quote_id, &voter, vote));
+                }
+                Task::TopQuotes {
+                    tx,
+                    mut filter,
+                    limit,
+                } => {
+                    filter.author = filter.author.map(|a| a.to_ascii_lowercase());
+                    let _e = tx.send(self.yield_quotes(
+                        &format!("select {QUOTE_COLUMNS} from quotes where {FILTER} and {MATCHES_QUERY} order by score desc, id asc limit ?6"),
+                        params![filter.author, filter.query, filter.channel_list(), filter.after, filter.before, limit],
+                    ));
+                }
+                Task::QuoteStats {
+                    tx,
+                    channels,
+                    limit,
+                } => {
+                    let _e = tx.send(self.quote_stats(channels, limit));
+                }
+                Task::UserStats { tx, user, channels } => {
+                    let _e = tx.send(self.user_stats(&user.to_ascii_lowercase(), channels));
+                }
+                Task::SetOptOut {
+                    tx,
+                    nick,
+                    opted_out,
+                } => {
+                    let _e = tx.send(self.set_opt_out(&nick, opted_out));
+                }
+                Task::OptedOut { tx } => {
+                    let _e = tx.send(opted_out(&self.db));
+                }
+                Task::ForgetUser { tx, nick } => {
+                    let _e = tx.send(self.forget_user(&nick));
+                }
+                Task::QuoteOfTheDay {
+                    tx,
+                    channel,
+                    channels,
+                    since,
+                    repeat_window,
+                } => {
+                    let result = self.quote_of_the_day(&channel, channels, since, repeat_window);
+                    let _e = tx.send(result);
+                }
+                Task::StartSearch {
+                    tx,
+                    user,
+                    channel,
+                    mut filter,
+                    limit,
+                } => {
+                    searches.retain(|_, s| s.last_used.elapsed().as_secs() < SEARCH_EXPIRY);
+                    filter.author = filter.author.map(|a| a.to_ascii_lowercase());
+                    let results = self.search(&filter, 0, limit);
+                    searches.insert(
+                        (user, channel),
+                        SearchSession {
+                            filter,
+                            page: 0,
+                            last_used: Instant::now(),
+                        },
+                    );
+                    let _e = tx.send(results);
+                }
+                Task::SearchPage {
+                    tx,
+                    user,
+                    channel,
+                    page,
+                    limit,
+                } => {
+                    searches.retain(|_, s| s.last_used.elapsed().as_secs() < SEARCH_EXPIRY);
+                    let session = searches.get_mut(&(user, channel));
+                    let _e = tx.send(self.search_page(session, page, limit));
+                }
+            }
+            tracing::debug!(
+                "task took {}ms",
+                Instant::now().duration_since(before).as_secs_f64() / 1000.0
+            );
+        }
+    }
+
+    /// Saves a quote unless it's a duplicate of a quote in `channels` (or any channel if `None`)
+    fn add_quote(
+        &self,
+        quote: &Quote,
+        channels: Option<&[String]>,
+    ) -> rusqlite::Result<AddedQuote> {
+        let tx = self.db.unchecked_transaction()?;
+        let added = insert_quote(&tx, quote, channels)?;
+        tx.commit()?;
+        Ok(added)
+    }
+
+    /// Picks a random quote, quotes with a higher score are picked more often.
+    /// Every upvote adds one to the weight of a quote, quotes with a negative score get the lowest weight.
+    fn weighted_random_quote(&self, filter: &QuoteFilter) -> rusqlite::Result<Option<Quote>> {
+        let mut stmt = self
+            .db
+            .prepare(&format!("select id, score from quotes where {FILTER} and {MATCHES_QUERY}"))?;
+        let candidates = stmt
+            .query_map(params![filter.author, filter.query, filter.channel_list(), filter.after, filter.before], |row| {
+                Ok((row.get::<_, i64>(0)?, row.get::<_, i64>(1)?))
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        let Ok(dist) = WeightedIndex::new(candidates.iter().map(|(_, score)| (score + 2).max(1))) else {
+            return Ok(None);
+        };
+        let id = candidates[dist.sample(&mut rand::thread_rng())].0;
+        self.query_quote(
+            &format!("select {QUOTE_COLUMNS} from quotes where id = ?"),
+            params![id],
+        )
+    }
+
+    /// Records a vote and returns the new score of the quote, or `None` if it doesn't exist.
+    /// Voting again replaces the previous vote.
+    fn vote(&self, quote_id: i64, voter: &str, vote: i64) -> rusqlite::Result<Option<i64>> {
+        let exists = self
+            .db
+            .query_row("select 1 from quotes where id = ?", params![quote_id], |_| Ok(()))
+            .optional()?
+            .is_some();
+        if !exists {
+            return Ok(None);
+        }
+        self.db.execute(
+            "insert into votes(quote_id, voter, vote) values(?,?,?) on conflict(quote_id, voter) do update set vote = excluded.vote where vote != excluded.vote",
+            params![quote_id, voter, vote],
+        )?;
+        self.db
+            .query_row("select score from quotes where id = ?", params![quote_id], |row| row.get(0))
+            .map(Some)
+    }
+
+    /// Deletes the last quote grabbed by `grabbed_by` in `channel` if it was grabbed at most `max_age` seconds ago
+    fn undo_grab(
+        &self,
+        grabbed_by: &str,
+        channel: &str,
+        max_age: u64,
+    ) -> rusqlite::Result<Option<Quote>> {
+        let quote = self.query_quote(
+            &format!("select {QUOTE_COLUMNS} from quotes where grabbed_by = ? collate nocase and channel = ? collate nocase and timestamp >= strftime('%s','now') - ? order by id desc limit 1"),
+            params![grabbed_by, channel, max_age],
+        )?;
+        if let Some(quote) = &quote {
+            self.db
+                .execute("delete from quotes where id = ?", params![quote.id])?;
+        }
+        Ok(quote)
+    }
+
+    /// Counts the quotes of `channels`, or of all channels if it's `None`
+    fn quote_stats(
+        &self,
+        channels: Option<Vec<String>>,
+        limit: usize,
+    ) -> rusqlite::Result<QuoteStats> {
+        let filter = QuoteFilter {
+            channels,
+            ..QuoteFilter::default()
+        };
+        let ranking = |sql: &str| -> rusqlite::Result<Vec<(String, u64)>> {
+            let mut stmt = self.db.prepare(sql)?;
+            let rows = stmt.query_map(params![filter.author, filter.query, filter.channel_list(), filter.after, filter.before, limit], |row| Ok((row.get(0)?, row.get(1)?)))?;
+            rows.collect()
+        };
+        Ok(QuoteStats {
+            quotes: self.db.query_row(
+                &format!("select count(*) from quotes where {FILTER}"),
+                params![filter.author, filter.query, filter.channel_list(), filter.after, filter.before],
+                |row| row.get(0),
+            )?,
+            // every speaker of a conversation is counted once
+            authors: ranking(&format!("select author, count(*) as n from (select author from quotes where id not in (select quote_id from quote_lines) and {FILTER} union all select lower(speaker) from quote_lines where quote_id in (select id from quotes where {FILTER}) group by quote_id, speaker) group by author order by n desc, author limit ?6"))?,
+            grabbers: ranking(&format!("select lower(grabbed_by) as grabber, count(*) as n from quotes where grabbed_by is not null and {FILTER} group by grabber order by n desc, grabber limit ?6"))?,
+        })
+    }
+
+    /// Counts the quotes of `user` in `channels`, or in all channels if it's `None`
+    fn user_stats(
+        &self,
+        user: &str,
+        channels: Option<Vec<String>>,
+    ) -> rusqlite::Result<UserStats> {
+        let mut filter = QuoteFilter {
+            author: Some(user.into()),
+            channels,
+            ..QuoteFilter::default()
+        };
+        let (quotes, first, last) = self.db.query_row(
+            &format!("select count(*), min(timestamp), max(timestamp) from quotes where {FILTER}"),
+            params![filter.author, filter.query, filter.channel_list(), filter.after, filter.before],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+        )?;
+        filter.author = None;
+        Ok(UserStats {
+            quotes,
+            grabbed: self.db.query_row(
+                &format!("select count(*) from quotes where grabbed_by = ?6 collate nocase and {FILTER}"),
+                params![filter.author, filter.query, filter.channel_list(), filter.after, filter.before, user],
+                |row| row.get(0),
+            )?,
+            first,
+            last,
+        })
+    }
+
+    /// Adds or removes `nick` from the opt-out list, returns `false` if it was already (not) on it
+    fn set_opt_out(&self, nick: &str, opted_out: bool) -> rusqlite::Result<bool> {
+        let changed = if opted_out {
+            self.db
+                .execute("insert or ignore into opt_outs(nick) values(?)", params![nick])?
+        } else {
+            self.db
+                .execute("delete from opt_outs where nick = ?", params![nick])?
+        };
+        Ok(changed > 0)
+    }
+
+    /// Deletes the quotes of `nick` (including conversations they took part in) and their votes,
+    /// removes their name from the quotes they grabbed and opts them out.
+    /// Returns the number of deleted quotes.
+    fn forget_user(&self, nick: &str) -> rusqlite::Result<usize> {
+        let filter = QuoteFilter {
+            author: Some(nick.to_ascii_lowercase()),
+            ..QuoteFilter::default()
+        };
+        let tx = self.db.unchecked_transaction()?;
+        let deleted = tx.execute(
+            &format!("delete from quotes where {FILTER}"),
+            params![filter.author, filter.query, filter.channel_list(), filter.after, filter.before],
+        )?;
+        tx.execute("delete from votes where voter = ?", params![nick])?;
+        tx.execute(
+            "update quotes set grabbed_by = null where grabbed_by = ? collate nocase",
+            params![nick],
+        )?;
+        tx.execute("insert or ignore into opt_outs(nick) values(?)", params![nick])?;
+        tx.commit()?;
+        Ok(deleted)
+    }
+
+    /// Returns the quote of the day of `channel` if it was picked after `since`.
+    /// Otherwise picks a random quote from `channels` that wasn't posted in `channel`
+    /// in the last `repeat_window` seconds.
+    fn quote_of_the_day(
+        &self,
+        channel: &str,
+        channels: Vec<String>,
+        since: i64,
+        repeat_window: u64,
+    ) -> rusqlite::Result<Option<Quote>> {
+        let current = self.query_quote(
+            &format!("select {QUOTE_COLUMNS} from qotd join quotes on quotes.id = qotd.quote_id where qotd.channel = ?1 and qotd.timestamp >= ?2 order by qotd.timestamp desc limit 1"),
+            params![channel, since],
+        )?;
+        if current.is_some() {
+            return Ok(current);
+        }
+        let filter = QuoteFilter {
+            channels: Some(channels),
+            ..QuoteFilter::default()
+        };
+        let repeat_after =
+            unix_now().saturating_sub(i64::try_from(repeat_window).unwrap_or(i64::MAX));
+        let quote = self.query_quote(
+            &format!("select {QUOTE_COLUMNS} from quotes where {FILTER} and quotes.id not in (select quote_id from qotd where channel = ?6 and timestamp > ?7) order by random() limit 1"),
+            params![filter.author, filter.query, filter.channel_list(), filter.after, filter.before, channel, repeat_after],
+        )?;
+        if let Some(q) = &quote {
+            self.db.execute(
+                "insert into qotd(channel, quote_id) values(?, ?)",
+                params![channel, q.id],
+            )?;
+        }
+        Ok(quote)
+    }
+
+    /// Returns a page of quotes matching `filter`, ranked with bm25 if it has a query
+    fn search(
+        &self,
+        filter: &QuoteFilter,
+        page: usize,
+        limit: usize,
+    ) -> rusqlite::Result<Vec<SearchResult>> {
+        let sql = if filter.query.is_some() {
+            format!("select {QUOTE_COLUMNS}, snippet(quotes_fts, 1, char(2), char(2), '...', 16) from quotes_fts join quotes on quotes.id = quotes_fts.rowid where quotes_fts.quote match ?2 and {FILTER} order by bm25(quotes_fts), quotes.id limit ?6 offset ?7")
+        } else {
+            format!("select {QUOTE_COLUMNS}, quotes.quote from quotes where {FILTER} order by quotes.id desc limit ?6 offset ?7")
+        };
+        let mut stmt = self.db.prepare(&sql)?;
+        let mut rows = stmt.query(params![
+            filter.author,
+            filter.query,
+            filter.channel_list(),
+            filter.after,
+            filter.before,
+            limit,
+            page * limit
+        ])?;
+        let mut results = Vec::new();
+        while let Some(row) = rows.next()? {
+            let mut quote = Quote::from_row(row)?;
+            load_lines(&self.db, &mut quote)?;
+            results.push(SearchResult {
+                quote,
+                snippet: row.get(7)?,
+            });
+        }
+        Ok(results)
+    }
+
+    /// Moves to another page of the user's search, returns `None` if they haven't searched recently.
+    /// The page is only changed if it has any results.
+    fn search_page(
+        &self,
+        session: Option<&mut SearchSession>,
+        page: Page,
+        limit: usize,
+    ) -> rusqlite::Result<Option<(usize, Vec<SearchResult>)>> {
+        let Some(session) = session else {
+            return Ok(None);
+        };
+        session.last_used = Instant::now();
+        let page = match page {
+            Page::Next => session.page + 1,
+            Page::Previous => session.page.saturating_sub(1),
+            Page::Jump(page) => page,
+        };
+        let results = self.search(&session.filter, page, limit)?;
+        if !results.is_empty() {
+            session.page = page;
+        }
+        Ok(Some((page, results)))
+    }
+
+    fn yield_quotes<P: Params>(&self, sql: &str, params: P) -> rusqlite::Result<Vec<Quote>> {
+        let mut quotes = self.db.prepare(sql).and_then(|mut v| {
+            v.query(params).and_then(|mut v| {
+                let mut quotes: Vec<Quote> = Vec::new();
+                while let Some(row) = v.next()? {
+                    quotes.push(Quote::from_row(row)?);
+                }
+                Ok(quotes)
+            })
+        })?;
+        for quote in &mut quotes {
+            load_lines(&self.db, quote)?;
+        }
+        Ok(quotes)
+    }
+
+    /// Returns the first quote selected by `sql`, with its conversation lines
+    fn query_quote<P: Params>(&self, sql: &str, params: P) -> rusqlite::Result<Option<Quote>> {
+        let Some(mut quote) = self.db.query_row(sql, params, Quote::from_row).optional()? else {
+            return Ok(None);
+        };
+        load_lines(&self.db, &mut quote)?;
+        Ok(Some(quote))
+    }
+}
+
+/// Returns the nicks of users who opted out of being quoted
+fn opted_out(db: &Connection) -> rusqlite::Result<Vec<String>> {
+    let mut stmt = db.prepare("select nick from opt_outs")?;
+    let nicks = stmt.query_map([], |row| row.get(0))?.collect();
+    nicks
+}
+
+/// Saves a quote with its conversation lines, unless it's a duplicate of a quote in `channels`.
+/// The id of the quote is kept if it's not taken yet.
+fn insert_quote(
+    db: &Connection,
+    quote: &Quote,
+    channels: Option<&[String]>,
+) -> rusqlite::Result<AddedQuote> {
+    if let Some(id) = find_duplicate(db, quote, channels)? {
+        return Ok(AddedQuote::Duplicate(id));
+    }
+    let id_taken = match quote.id {
+        Some(id) => db
+            .query_row("select 1 from quotes where id = ?", params![id], |_| Ok(()))
+            .optional()?
+            .is_some(),
+        None => false,
+    };
+    db.execute(
+        "insert into quotes(id,author,quote,channel,grabbed_by,timestamp,score) values(?,?,?,?,?,coalesce(?,strftime('%s','now')),?)",
+        params![quote.id.filter(|_| !id_taken), quote.author, quote.quote, quote.channel, quote.grabbed_by, quote.timestamp, quote.score],
+    )?;
+    let id = db.last_insert_rowid();
+    for (i, line) in quote.lines.iter().enumerate() {
+        db.execute(
+            "insert into quote_lines(quote_id, line, speaker, message) values(?,?,?,?)",
+            params![id, i, line.speaker, line.message],
+        )?;
+    }
+    Ok(AddedQuote::Added(id))
+}
+
+/// Looks for a quote in `channels` (or any channel if `None`) by the same author with the same text
+/// (ignoring case and whitespace), or one grabbed around the same time that shares a message with `quote`
+fn find_duplicate(
+    db: &Connection,
+    quote: &Quote,
+    channels: Option<&[String]>,
+) -> rusqlite::Result<Option<i64>> {
+    let text = normalize(&quote.quote);
+    let messages: Vec<String> = quote.quote.split(" | ").map(normalize).collect();
+    let timestamp = quote.timestamp.unwrap_or_else(unix_now);
+    let mut stmt = db.prepare("select id, quote, timestamp from quotes where author = ?1 and (?2 is null or channel is null or instr(?2, ',' || lower(channel) || ',') > 0)")?;
+    let mut rows = stmt.query(params![quote.author, channels.map(channel_list)])?;
+    while let Some(row) = rows.next()? {
+        let existing: String = row.get(1)?;
+        if normalize(&existing) == text {
+            return Ok(Some(row.get(0)?));
+        }
+        let recent = matches!(row.get::<_, Option<i64>>(2)?, Some(t) if (t - timestamp).abs() <= DUPLICATE_WINDOW);
+        if recent && existing.split(" | ").any(|m| messages.contains(&normalize(m))) {
+            return Ok(Some(row.get(0)?));
+        }
+    }
+    Ok(None)
+}
+
+fn load_lines(db: &Connection, quote: &mut Quote) -> rusqlite::Result<()> {
+    let mut stmt = db.prepare_cached(
+        "select speaker, message from quote_lines where quote_id = ? order by line",
+    )?;
+    quote.lines = stmt
+        .query_map(params![quote.id], |row| {
+            Ok(QuoteLine {
+                speaker: row.get(0)?,
+                message: row.get(1)?,
+            })
+        })?
+        .collect::<rusqlite::Result<_>>()?;
+    Ok(())
+}
+
+#[derive(Debug)]
+pub struct DbStats {
+    pub quotes: u64,
+    pub authors: u64,
+    /// Size of the database in bytes
+    pub size: u64,
+    /// Size of unused pages in bytes, which can be reclaimed with [`DbMaintenance::vacuum`]
+    pub free: u64,
+}
+
+/// Direct access to the database for maintenance tasks, used while the bot isn't running
+pub struct DbMaintenance {
+    db: Connection,
+}
+
+impl DbMaintenance {
+    /// Opens an existing database without migrating it, fails if it doesn't exist
+    pub fn open(dbpath: impl AsRef<Path>) -> rusqlite::Result<Self> {
+        let flags = OpenFlags::default() & !OpenFlags::SQLITE_OPEN_CREATE;
+        let db = Connection::open_with_flags(dbpath, flags)?;
+        Ok(Self { db })
+    }
+
+    /// Opens the database, creating it if it doesn't exist yet
+    pub fn create(dbpath: impl AsRef<Path>) -> rusqlite::Result<Self> {
+        Ok(Self {
+            db: Connection::open(dbpath)?,
+        })
+    }
+
+    /// Returns the number of schema migrations that will be applied by [`DbMaintenance::migrate`]
+    pub fn pending_migrations(&self) -> anyhow::Result<usize> {
+        migrations::pending(&self.db)
+    }
+
+    pub fn migrate(&mut self) -> anyhow::Result<()> {
+        migrations::migrate(&mut self.db)
+    }
+
+    /// Runs `pragma quick_check` and returns the problems it found
+    pub fn check(&self) -> rusqlite::Result<Vec<String>> {
+        let mut stmt = self.db.prepare("pragma quick_check")?;
+        let problems = stmt
+            .query_map([], |row| row.get::<_, String>(0))?
+            .filter(|r| !matches!(r.as_deref(), Ok("ok")))
+            .collect();
+        problems
+    }
+
+    /// Works on databases that weren't migrated yet
+    pub fn stats(&self) -> rusqlite::Result<DbStats> {
+        let pragma = |name: &str| {
+            self.db
+                .query_row(&format!("pragma {name}"), [], |row| row.get::<_, u64>(0))
+        };
+        let page_size = pragma("page_size")?;
+        // the schema used before migrations were introduced calls authors `username`
+        let author = if migrations::version(&self.db)? == 0 {
+            "username"
+        } else {
+            "author"
+        };
+        Ok(DbStats {
+            quotes: self
+                .db
+                .query_row("select count(*) from quotes", [], |row| row.get(0))?,
+            authors: self.db.query_row(
+                &format!("select count(distinct {author}) from quotes"),
+                [],
+                |row| row.get(0),
+            )?,
+            size: pragma("page_count")? * page_size,
+            free: pragma("freelist_count")? * page_size,
+        })
+    }
+
+    /// Returns all quotes ordered by id, with the lines of conversations
+    pub fn export(&self) -> rusqlite::Result<Vec<Quote>> {
+        let mut stmt = self
+            .db
+            .prepare(&format!("select {QUOTE_COLUMNS} from quotes order by id"))?;
+        let mut quotes = stmt
+            .query_map([], Quote::from_row)?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        for quote in &mut quotes {
+            load_lines(&self.db, quote)?;
+        }
+        Ok(quotes)
+    }
+
+    /// Returns the nicks of users who opted out of being quoted
+    pub fn opted_out(&self) -> rusqlite::Result<Vec<String>> {
+        opted_out(&self.db)
+    }
+
+    /// Adds quotes in a single transaction, skipping duplicates.
+    /// Returns the number of added quotes and the number of duplicates.
+    pub fn import(&mut self, quotes: Vec<Quote>) -> rusqlite::Result<(usize, usize)> {
+        let tx = self.db.transaction()?;
+        let (mut added, mut duplicates) = (0, 0);
+        for mut quote in quotes {
+            quote.author.make_ascii_lowercase();
+            // imported quotes are only compared with quotes of the same channel
+            let channels = quote.channel.as_ref().map(std::slice::from_ref);
+            match insert_quote(&tx, &quote, channels)? {
+                AddedQuote::Added(_) => added += 1,
+                AddedQuote::Duplicate(_) => duplicates += 1,
+            }
+        }
+        tx.commit()?;
+        Ok((added, duplicates))
+    }
+
+    /// Rebuilds the database file, reclaiming unused space
+    pub fn vacuum(&self) -> rusqlite::Result<()> {
+        self.db.execute_batch("vacuum")
+    }
+
+    /// Writes a consistent copy of the database to `dest`, which must not exist yet
+    pub fn backup(&self, dest: &Path) -> anyhow::Result<()> {
+        let Some(dest) = dest.to_str() else {
+            anyhow::bail!("The backup path must be valid UTF-8");
+        };
+        self.db.execute("vacuum into ?", params![dest])?;
+        Ok(())
+    }
+}
@@ -0,0 +1,99 @@
+//! Reading and writing quotes as JSON or CSV, used for importing and exporting quotes
+use super::Quote;
+use serde::{Deserialize, Serialize};
+use std::{
+    io::{Read, Write},
+    path::Path,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// Array of quotes, including the lines of conversations
+    Json,
+    /// One quote per row, conversations only keep their text
+    Csv,
+}
+
+impl Format {
+    /// Picks the format based on the extension of `path`
+    pub fn from_path(path: &Path) -> anyhow::Result<Self> {
+        let extension = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(str::to_ascii_lowercase);
+        match extension.as_deref() {
+            Some("json") => Ok(Self::Json),
+            Some("csv") => Ok(Self::Csv),
+            _ => anyhow::bail!("Unknown file format, expected a .json or .csv file"),
+        }
+    }
+}
+
+/// Row of a CSV file
+#[derive(Serialize, Deserialize)]
+struct CsvQuote {
+    id: Option<i64>,
+    author: String,
+    quote: String,
+    channel: Option<String>,
+    grabbed_by: Option<String>,
+    timestamp: Option<i64>,
+    #[serde(default)]
+    score: i64,
+}
+
+pub fn write_quotes(
+    format: Format,
+    quotes: &[Quote],
+    mut writer: impl Write,
+) -> anyhow::Result<()> {
+    match format {
+        Format::Json => serde_json::to_writer_pretty(&mut writer, quotes)?,
+        Format::Csv => {
+            let mut csv = csv::Writer::from_writer(&mut writer);
+            for q in quotes {
+                csv.serialize(CsvQuote {
+                    id: q.id,
+                    author: q.author.clone(),
+                    quote: q.quote.clone(),
+                    channel: q.channel.clone(),
+                    grabbed_by: q.grabbed_by.clone(),
+                    timestamp: q.timestamp,
+                    score: q.score,
+                })?;
+            }
+            csv.flush()?;
+        }
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+pub fn read_quotes(format: Format, reader: impl Read) -> anyhow::Result<Vec<Quote>> {
+    let quotes: Vec<Quote> = match format {
+        Format::Json => serde_json::from_reader(reader)?,
+        Format::Csv => csv::Reader::from_reader(reader)
+            .deserialize()
+            .map(|row| {
+                let row: CsvQuote = row?;
+                Ok(Quote {
+                    id: row.id,
+                    author: row.author,
+                    quote: row.quote,
+                    channel: row.channel,
+                    grabbed_by: row.grabbed_by,
+                    timestamp: row.timestamp,
+                    score: row.score,
+                    lines: Vec::new(),
+                })
+            })
+            .collect::<Result<_, csv::Error>>()?,
+    };
+    if let Some(i) = quotes
+        .iter()
+        .position(|q| q.author.trim().is_empty() || q.quote.trim().is_empty())
+    {
+        anyhow::bail!("Quote {} has no author or text", i + 1);
+    }
+    Ok(quotes)
+}
@@ -0,0 +1,99 @@
+use std::{
+    collections::HashMap,
+    error::Error,
+    fmt::{Display, Formatter},
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+/// An error caused by the user (e.g. invalid arguments).
+/// Its message is shown in the channel as-is and it's never reported to the admins.
+#[derive(Debug)]
+pub struct UserError(String);
+
+impl UserError {
+    pub fn new(msg: impl Into<String>) -> Self {
+        Self(msg.into())
+    }
+}
+
+impl Display for UserError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl Error for UserError {}
+
+/// Returns a short message describing an internal error that can be safely shown in the channel
+pub fn friendly_message(e: &anyhow::Error) -> String {
+    #[cfg(any(feature = "title", feature = "waifu"))]
+    if e.is::<reqwest::Error>() {
+        return "Couldn't reach the remote server.".into();
+    }
+    #[cfg(feature = "spotify")]
+    if e.is::<rspotify::ClientError>() {
+        return "Couldn't reach Spotify.".into();
+    }
+    if e.is::<rusqlite::Error>() {
+        "A database error has occurred.".into()
+    } else {
+        "An internal error has occurred.".into()
+    }
+}
+
+struct ReportState {
+    last_report: Instant,
+    suppressed: usize,
+}
+
+/// Deduplicates reports of internal errors sent to the admin channel
+pub struct ErrorReporter {
+    /// How long identical errors are suppressed after being reported
+    cooldown: Duration,
+    recent: Mutex<HashMap<String, ReportState>>,
+}
+
+impl ErrorReporter {
+    pub fn new(cooldown: Duration) -> Self {
+        Self {
+            cooldown,
+            recent: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Returns the message that should be sent to the admin channel,
+    /// or `None` if an identical error was reported recently.
+    pub fn prepare(&self, key: String, report: &str) -> Option<String> {
+        let now = Instant::now();
+        let mut recent = self.recent.lock().unwrap();
+        let suppressed = match recent.get_mut(&key) {
+            Some(state) if now.duration_since(state.last_report) < self.cooldown => {
+                state.suppressed += 1;
+                return None;
+            }
+            Some(state) => {
+                state.last_report = now;
+                std::mem::take(&mut state.suppressed)
+            }
+            None => {
+                // forget errors that haven't occurred for a while
+                recent.retain(|_, s| now.duration_since(s.last_report) < self.cooldown);
+                recent.insert(
+                    key,
+                    ReportState {
+                        last_report: now,
+                        suppressed: 0,
+                    },
+                );
+                0
+            }
+        };
+        let msg = if suppressed > 0 {
+            format!("{report} (+{suppressed} similar errors suppressed)")
+        } else {
+            report.to_string()
+        };
+        Some(msg)
+    }
+}
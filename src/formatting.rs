@@ -0,0 +1,26 @@
+/// Removes IRC formatting codes (bold, colors, italics etc.) from a message
+pub fn strip_formatting(msg: &str) -> String {
+    let mut result = String::with_capacity(msg.len());
+    let mut chars = msg.chars().peekable();
+    while let Some(ch) = chars.next() {
+        match ch {
+            '\x02' | '\x0f' | '\x11' | '\x16' | '\x1d' | '\x1e' | '\x1f' => {}
+            '\x03' => {
+                // color code: up to 2 digits of foreground, optionally followed by a comma
+                // and up to 2 digits of background
+                let has_foreground = chars.next_if(char::is_ascii_digit).is_some();
+                chars.next_if(char::is_ascii_digit);
+                let mut lookahead = chars.clone();
+                if has_foreground
+                    && lookahead.next() == Some(',')
+                    && matches!(lookahead.next(), Some(c) if c.is_ascii_digit())
+                {
+                    chars = lookahead;
+                    chars.next_if(char::is_ascii_digit);
+                }
+            }
+            _ => result.push(ch),
+        }
+    }
+    result
+}
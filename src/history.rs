@@ -1,8 +1,13 @@
-use std::collections::{HashMap, VecDeque};
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{Arc, RwLock as SyncRwLock},
+};
 use tokio::sync::RwLock;
 
 pub struct MessageHistory {
     map: RwLock<HashMap<String, VecDeque<String>>>,
+    /// Lowercased channel -> (author, message), in the order the messages were sent
+    channels: RwLock<HashMap<String, VecDeque<(String, String)>>>,
     maxlen: usize,
 }
 
@@ -10,6 +15,7 @@ impl MessageHistory {
     pub fn new(maxlen: usize) -> MessageHistory {
         MessageHistory {
             map: RwLock::new(HashMap::new()),
+            channels: RwLock::new(HashMap::new()),
             maxlen,
         }
     }
@@ -21,34 +27,137 @@ impl MessageHistory {
             .map(ToString::to_string)
     }
 
-    pub async fn last_msgs(&self, user: &str, count: usize) -> Option<Vec<String>> {
-        let map = self.map.read().await;
-        if let Some(deque) = map.get(user) {
-            let len = deque.len();
-            let count = len.min(count);
-            Some(
-                deque
-                    .range(len - count..)
-                    .map(ToString::to_string)
-                    .collect(),
-            )
-        } else {
-            None
+    /// Returns the last `count` messages of `user` sent in `channel`, oldest first
+    pub async fn last_msgs(&self, channel: &str, user: &str, count: usize) -> Option<Vec<String>> {
+        let mut msgs = self.user_channel_msgs(channel, user).await?;
+        msgs.drain(..msgs.len().saturating_sub(count));
+        Some(msgs)
+    }
+
+    /// Returns the most recent message of `user` in `channel` matching `filter`,
+    /// preceded by up to `count - 1` earlier messages
+    pub async fn last_msgs_matching(
+        &self,
+        channel: &str,
+        user: &str,
+        count: usize,
+        filter: impl Fn(&str) -> bool,
+    ) -> Option<Vec<String>> {
+        let mut msgs = self.user_channel_msgs(channel, user).await?;
+        let end = msgs.iter().rposition(|m| filter(m))? + 1;
+        msgs.truncate(end);
+        msgs.drain(..end.saturating_sub(count));
+        Some(msgs)
+    }
+
+    /// Returns all messages of `user` still stored for `channel`, oldest first
+    async fn user_channel_msgs(&self, channel: &str, user: &str) -> Option<Vec<String>> {
+        let channels = self.channels.read().await;
+        let msgs: Vec<String> = channels
+            .get(&channel.to_ascii_lowercase())?
+            .iter()
+            .filter(|(author, _)| author == user)
+            .map(|(_, message)| message.clone())
+            .collect();
+        (!msgs.is_empty()).then_some(msgs)
+    }
+
+    /// Returns the last `count` messages sent in `channel` as (author, message) pairs, oldest first.
+    /// If `authors` isn't empty, only messages from these users are returned.
+    pub async fn last_channel_msgs(
+        &self,
+        channel: &str,
+        count: usize,
+        authors: &[&str],
+    ) -> Vec<(String, String)> {
+        let channels = self.channels.read().await;
+        let Some(deque) = channels.get(&channel.to_ascii_lowercase()) else {
+            return Vec::new();
+        };
+        let mut msgs: Vec<(String, String)> = deque
+            .iter()
+            .rev()
+            .filter(|(author, _)| {
+                authors.is_empty() || authors.iter().any(|a| a.eq_ignore_ascii_case(author))
+            })
+            .take(count)
+            .cloned()
+            .collect();
+        msgs.reverse();
+        msgs
+    }
+
+    /// Returns the last message of each of `authors` sent in `channel`, oldest first.
+    /// Authors without messages in the channel are skipped.
+    pub async fn last_channel_msg_of_each(
+        &self,
+        channel: &str,
+        authors: &[&str],
+    ) -> Vec<(String, String)> {
+        let channels = self.channels.read().await;
+        let Some(deque) = channels.get(&channel.to_ascii_lowercase()) else {
+            return Vec::new();
+        };
+        let mut msgs: Vec<(String, String)> = Vec::new();
+        for (author, message) in deque.iter().rev() {
+            let wanted = authors.iter().any(|a| a.eq_ignore_ascii_case(author));
+            if wanted && !msgs.iter().any(|(a, _)| a.eq_ignore_ascii_case(author)) {
+                msgs.push((author.clone(), message.clone()));
+            }
         }
+        msgs.reverse();
+        msgs
     }
 
-    pub async fn edit_message(&self, user: &str, depth: usize, edited: String) -> bool {
+    /// Replaces a message of `user`, `depth` messages before their last one.
+    /// The same message is also replaced in the history of `channel`.
+    pub async fn edit_message(
+        &self,
+        channel: &str,
+        user: &str,
+        depth: usize,
+        edited: String,
+    ) -> bool {
         let mut map = self.map.write().await;
-        if let Some(deque) = map.get_mut(user) {
-            if let Some(old) = deque.get_mut(deque.len() - 1 - depth) {
-                *old = edited;
-                return true;
+        let Some(old) = map
+            .get_mut(user)
+            .and_then(|deque| deque.iter_mut().rev().nth(depth))
+        else {
+            return false;
+        };
+        let mut channels = self.channels.write().await;
+        if let Some(deque) = channels.get_mut(&channel.to_ascii_lowercase()) {
+            if let Some((_, message)) = deque
+                .iter_mut()
+                .rev()
+                .find(|(author, message)| author == user && message == old)
+            {
+                message.clone_from(&edited);
             }
         }
-        false
+        *old = edited;
+        true
     }
 
-    pub async fn add_message(&self, user: &str, message: String) {
+    /// Removes all messages of `user`
+    pub async fn forget(&self, user: &str) {
+        self.map.write().await.remove(user);
+        for deque in self.channels.write().await.values_mut() {
+            deque.retain(|(author, _)| author != user);
+        }
+    }
+
+    pub async fn add_message(&self, channel: &str, user: &str, message: String) {
+        {
+            let mut channels = self.channels.write().await;
+            let deque = channels
+                .entry(channel.to_ascii_lowercase())
+                .or_insert_with(|| VecDeque::with_capacity(self.maxlen));
+            if deque.len() == self.maxlen {
+                deque.pop_front();
+            }
+            deque.push_back((user.to_string(), message.clone()));
+        }
         let mut map = self.map.write().await;
         if let Some(deque) = map.get_mut(user) {
             if deque.len() == self.maxlen {
@@ -62,3 +171,23 @@ impl MessageHistory {
         }
     }
 }
+
+/// Message histories of the bots of all networks, so users can be forgotten everywhere at once
+#[derive(Default)]
+pub struct HistoryRegistry {
+    histories: SyncRwLock<Vec<Arc<MessageHistory>>>,
+}
+
+impl HistoryRegistry {
+    pub fn register(&self, history: Arc<MessageHistory>) {
+        self.histories.write().unwrap().push(history);
+    }
+
+    /// Removes all messages of `user` from the histories of all networks
+    pub async fn forget(&self, user: &str) {
+        let histories = self.histories.read().unwrap().clone();
+        for history in histories {
+            history.forget(user).await;
+        }
+    }
+}
@@ -0,0 +1,31 @@
+#![warn(clippy::pedantic)]
+#![allow(
+    clippy::module_name_repetitions,
+    clippy::too_many_lines,
+    clippy::missing_errors_doc,
+    clippy::missing_panics_doc,
+    clippy::must_use_candidate
+)]
+
+pub mod bot;
+pub mod builder;
+pub mod commands;
+pub mod config;
+pub mod database;
+pub mod error;
+pub mod formatting;
+pub mod history;
+pub mod optout;
+pub mod ratelimit;
+pub mod regex_util;
+pub mod reload;
+pub mod transport;
+#[cfg(feature = "web")]
+pub mod web;
+
+pub use async_trait::async_trait;
+pub use bot::{Bot, BotSettings, Command, CommandContext, Trigger, TriggerContext};
+pub use builder::BotBuilder;
+pub use config::UberConfig;
+pub use database::{DbExecutor, ExecutorConnection};
+pub use error::UserError;
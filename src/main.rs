@@ -1,266 +1,228 @@
 #![warn(clippy::pedantic)]
-#![allow(clippy::module_name_repetitions, clippy::too_many_lines)]
 
-use fancy_regex::Regex;
-use std::{env, fs, process, str::FromStr, sync::Arc, thread};
+mod cli;
 
-use crate::{
-    bot::Bot,
-    commands::{
-        eval::Eval,
-        help::Help,
-        leek::{Leet, Mock, Owo},
-        quotes::{Grab, Quot, Search, SearchNext},
-        sed::Sed,
-        spotify::Spotify,
-        title::Title,
-        waifu::Waifu,
-    },
-    web::HttpContext,
-};
-use futures_util::stream::StreamExt;
-use irc::{
-    client::{prelude::Config, Client, ClientStream},
-    proto::{Capability, ChannelExt, Command, Prefix},
-};
-use rspotify::Credentials;
-use tokio::{
-    select,
-    sync::{broadcast, mpsc, mpsc::unbounded_channel},
+use anyhow::Context;
+use cli::{Args, Command, ConsoleArgs, DbCommand, USAGE};
+use std::{
+    env,
+    fs::File,
+    io::{self, BufReader, BufWriter},
+    path::{Path, PathBuf},
+    str::FromStr,
 };
 use tracing::Level;
-
-use crate::{
-    config::UberConfig,
-    database::{DbExecutor, ExecutorConnection},
+use uberbot::{
+    database::{
+        logs::{date_from_path, read_log_quotes, LogQuotes},
+        transfer::{read_quotes, write_quotes, Format},
+        DbMaintenance,
+    },
+    transport::split_target,
+    BotBuilder, UberConfig,
 };
 
-mod bot;
-mod commands;
-mod config;
-mod database;
-mod history;
-mod regex_util;
-mod web;
-
-#[cfg(unix)]
-async fn terminate_signal() {
-    use tokio::signal::unix::{signal, SignalKind};
-    let mut sigterm = signal(SignalKind::terminate()).unwrap();
-    let mut sigint = signal(SignalKind::interrupt()).unwrap();
-    tracing::debug!("Installed ctrl+c handler");
-    select! {
-        _ = sigterm.recv() => (),
-        _ = sigint.recv() => ()
-    }
-}
-
-#[cfg(windows)]
-async fn terminate_signal() {
-    use tokio::signal::windows::ctrl_c;
-    let mut ctrlc = ctrl_c().unwrap();
-    tracing::debug!("Installed ctrl+c handler");
-    let _ = ctrlc.recv().await;
-}
-
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
-    let config_var = env::var("UBERBOT_CONFIG");
-    let config_path = config_var.as_deref().unwrap_or("uberbot.toml");
-    println!("Loading config from '{config_path}'...");
-    let config_str = fs::read_to_string(config_path)?;
-    let cfg: UberConfig = toml::from_str(&config_str)?;
-
-    tracing_subscriber::fmt::fmt()
-        .with_max_level({
-            if let Some(o) = cfg.log_level.as_deref() {
-                Level::from_str(o)?
-            } else {
-                Level::INFO
-            }
-        })
-        .init();
-
-    if cfg.bot.prefixes.is_empty() {
-        tracing::error!("You have to specify at least one prefix");
-        process::exit(1);
-    }
-
-    let (db_exec, db_conn) =
-        DbExecutor::create(cfg.bot.db_path.as_deref().unwrap_or("uberbot.db3"))?;
-    let exec_thread = thread::spawn(move || db_exec.run());
-
-    let uber_ver = concat!("Überbot ", env!("CARGO_PKG_VERSION"));
-    let nick = cfg.irc.nickname.unwrap_or_else(|| cfg.irc.username.clone());
-    let irc_config = Config {
-        alt_nicks: cfg
-            .irc
-            .alt_nicks
-            .unwrap_or_else(|| vec![format!("{}_", &nick), format!("{}__", &nick)]),
-        nickname: Some(nick),
-        username: Some(cfg.irc.username.clone()),
-        realname: Some(cfg.irc.username.clone()),
-        server: Some(cfg.irc.host),
-        port: Some(cfg.irc.port),
-        use_tls: Some(cfg.irc.tls),
-        channels: cfg.irc.channels,
-        umodes: cfg.irc.mode,
-        user_info: Some(uber_ver.into()),
-        version: Some(uber_ver.into()),
-        ..Config::default()
+    let args = match Args::parse(env::args().skip(1)) {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("{e}\n\n{USAGE}");
+            std::process::exit(2);
+        }
     };
-    let mut client = Client::from_config(irc_config).await?;
-    let stream = client.stream()?;
-
-    if let Some(pass) = cfg.irc.sasl_pass {
-        let sasl_pass = base64::encode(format!(
-            "{}\0{}\0{}",
-            &cfg.irc.username, &cfg.irc.username, pass
-        ));
+    match args.command {
+        Command::Help => {
+            println!("{USAGE}");
+            return Ok(());
+        }
+        Command::Version => {
+            println!("uberbot {}", env!("CARGO_PKG_VERSION"));
+            return Ok(());
+        }
+        _ => {}
+    }
 
-        client.send_cap_req(&[Capability::Sasl])?;
-        client.send(Command::NICK(client.current_nickname().into()))?;
-        client.send(Command::USER(
-            cfg.irc.username.clone(),
-            "0".into(),
-            cfg.irc.username.clone(),
-        ))?;
-        client.send_sasl_plain()?;
-        client.send_sasl(sasl_pass)?;
-        client.send(Command::CAP(
-            None,
-            irc::proto::CapSubCommand::END,
-            None,
-            None,
-        ))?;
+    let config_path = args.config.unwrap_or_else(|| {
+        env::var_os("UBERBOT_CONFIG").map_or_else(|| "uberbot.toml".into(), PathBuf::from)
+    });
+    eprintln!("Loading config from '{}'...", config_path.display());
+    let cfg = UberConfig::load(&config_path)?;
+
+    let subscriber = tracing_subscriber::fmt::fmt().with_max_level({
+        if let Some(o) = cfg.log_level.as_deref() {
+            Level::from_str(o)?
+        } else {
+            Level::INFO
+        }
+    });
+    // keep stdout clean for the console and the maintenance commands
+    if matches!(args.command, Command::Run { console: None }) {
+        subscriber.init();
     } else {
-        client.identify()?;
+        subscriber.with_writer(io::stderr).init();
     }
+    tracing::debug!("Loaded config: {:?}", cfg);
 
-    let client = Arc::new(client);
-
-    let (ctx, _) = broadcast::channel(1);
-    let (etx, mut erx) = unbounded_channel();
-
-    let sf = {
-        let client = client.clone();
-        move |target, msg| Ok(client.send_privmsg(target, msg)?)
-    };
-
-    let http_task = cfg.web.map(|http| {
-        let http_ctx = ctx.subscribe();
-        let context = HttpContext {
-            cfg: http,
-            sendmsg: sf.clone(),
-        };
-        tokio::spawn(async move {
-            if let Err(e) = web::run(context, http_ctx).await {
-                tracing::error!("Fatal error in web service: {}", e);
-            }
-        })
-    });
-    let mut bot = Bot::new(
-        cfg.bot.prefixes,
-        db_conn,
-        cfg.bot.history_depth,
-        sf,
-        cfg.bot.ignored_triggers,
-    );
+    match args.command {
+        Command::CheckConfig => check_config(cfg),
+        Command::Db(cmd) => db_command(&cfg, cmd),
+        Command::Run { console } => run(cfg, config_path, console).await,
+        Command::Help | Command::Version => unreachable!(),
+    }
+}
 
-    bot.add_command("help".into(), Help);
-    bot.add_command("waifu".into(), Waifu::default());
-    bot.add_command("owo".into(), Owo);
-    bot.add_command("leet".into(), Leet);
-    bot.add_command("mock".into(), Mock);
-    bot.add_command("ev".into(), Eval::default());
-    bot.add_command("grab".into(), Grab);
-    bot.add_command("quot".into(), Quot);
-    let search_limit = cfg.bot.search_limit.unwrap_or(3);
-    bot.add_command("qsearch".into(), Search::new(search_limit));
-    bot.add_command("qnext".into(), SearchNext::new(search_limit));
-    bot.add_trigger(
-        "sed".into(),
-        Regex::new(r"^(?:(?<u>\S+):\s+)?s/(?<r>[^/]*)/(?<w>[^/]*)(?:/(?<f>[a-z]*))?\s*")?,
-        Sed,
-    );
-    if let Some(spotcfg) = cfg.spotify {
-        let creds = Credentials::new(&spotcfg.client_id, &spotcfg.client_secret);
-        let spotify = Spotify::new(creds).await?;
-        bot.add_trigger("spotify".into(), Regex::new(r"(?:https?|spotify):(?://open\.spotify\.com/)?(track|artist|album|playlist)[/:]([a-zA-Z\d]*)")?, spotify);
+async fn run(
+    cfg: UberConfig,
+    config_path: PathBuf,
+    console: Option<ConsoleArgs>,
+) -> anyhow::Result<()> {
+    let mut builder = BotBuilder::new(cfg);
+    builder
+        .add_builtin_commands()
+        .await?
+        .config_path(config_path);
+    if let Some(ConsoleArgs { nick, channel }) = console {
+        let channel = channel
+            .or_else(|| {
+                let networks = builder.config().networks();
+                networks.first()?.1.channels.first().cloned()
+            })
+            .unwrap_or_else(|| "#console".into());
+        builder
+            .run_console(nick.unwrap_or_else(|| "console".into()), channel)
+            .await
     } else {
-        tracing::warn!("Spotify module is disabled, because the config is missing");
+        builder.run().await
     }
-    bot.add_trigger(
-        "title".into(),
-        Regex::new(
-            r"https?://[-a-zA-Z0-9@:%._+~#=]{1,}\.[a-zA-Z0-9()]{1,}\b[-a-zA-Z0-9()@:%_+.~#?&/=]*",
-        )?,
-        Title::new(cfg.bot.user_agent)?,
-    );
-    #[cfg(feature = "debug")]
-    {
-        use commands::debug::*;
-        bot.add_command("lastmsg".into(), LastMsg);
-        bot.add_command("sleep".into(), Sleep)
+}
+
+fn check_config(cfg: UberConfig) -> anyhow::Result<()> {
+    let warnings = BotBuilder::new(cfg).check()?;
+    for warning in &warnings {
+        println!("warning: {warning}");
     }
+    println!("Config OK");
+    Ok(())
+}
 
-    let message_loop_task = tokio::spawn(async move {
-        if let Err(e) = message_loop(stream, bot).await {
-            let _err = etx.send(e);
+fn db_command(cfg: &UberConfig, cmd: DbCommand) -> anyhow::Result<()> {
+    // quotes can be imported into a new database
+    let db = if matches!(cmd, DbCommand::Import(_) | DbCommand::ImportLog { .. }) {
+        DbMaintenance::create(cfg.db_path())
+    } else {
+        DbMaintenance::open(cfg.db_path())
+    };
+    let mut db = db.with_context(|| format!("Couldn't open the database '{}'", cfg.db_path()))?;
+    // imports and exports need the current schema, the other commands leave the database as it is
+    if matches!(
+        cmd,
+        DbCommand::Export(_) | DbCommand::Import(_) | DbCommand::ImportLog { .. }
+    ) {
+        db.migrate()?;
+    }
+    match cmd {
+        DbCommand::Stats => {
+            let pending = db.pending_migrations()?;
+            if pending > 0 {
+                println!("The database will be migrated ({pending} pending migrations)");
+            }
+            let stats = db.stats()?;
+            println!("Quotes:  {}", stats.quotes);
+            println!("Authors: {}", stats.authors);
+            println!("Size:    {} KiB", stats.size / 1024);
+            println!("Unused:  {} KiB", stats.free / 1024);
         }
-    });
-
-    select! {
-        _ = terminate_signal() => {
-            tracing::info!("Received shutdown signal, sending QUIT message");
-            client.send_quit("überbot shutting down")?;
+        DbCommand::Vacuum => {
+            let before = db.stats()?.size;
+            db.vacuum()?;
+            let after = db.stats()?.size;
+            println!(
+                "Vacuumed '{}', {} KiB reclaimed",
+                cfg.db_path(),
+                before.saturating_sub(after) / 1024
+            );
         }
-        e = erx.recv() => {
-            if let Some(e) = e {
-                tracing::error!("An error has occurred, shutting down: {}", e);
-            } else {
-                tracing::error!("Error channel has been dropped due to an unknown error, shutting down");
-            }
+        DbCommand::Backup(dest) => {
+            db.backup(&dest)?;
+            println!("Backed up '{}' to '{}'", cfg.db_path(), dest.display());
         }
+        DbCommand::Export(dest) => {
+            let format = Format::from_path(&dest)?;
+            let quotes = db.export()?;
+            let file = File::create(&dest)
+                .with_context(|| format!("Couldn't create '{}'", dest.display()))?;
+            write_quotes(format, &quotes, BufWriter::new(file))?;
+            println!("Exported {} quotes to '{}'", quotes.len(), dest.display());
+        }
+        DbCommand::Import(src) => {
+            let format = Format::from_path(&src)?;
+            let file =
+                File::open(&src).with_context(|| format!("Couldn't open '{}'", src.display()))?;
+            let quotes = read_quotes(format, BufReader::new(file))
+                .with_context(|| format!("Couldn't read quotes from '{}'", src.display()))?;
+            let (added, duplicates) = db.import(quotes)?;
+            println!(
+                "Imported {added} quotes from '{}', skipped {duplicates} duplicates",
+                src.display()
+            );
+        }
+        DbCommand::ImportLog { path, channel } => import_log(cfg, &mut db, &path, channel)?,
     }
-
-    tracing::info!("Closing services...");
-    let _ = ctx.send(());
-    message_loop_task.await.unwrap();
-    tracing::info!("Message loop finished");
-    if let Some(t) = http_task {
-        t.await.unwrap();
-        tracing::info!("Web service finished");
-    }
-    exec_thread.join().unwrap();
-    tracing::info!("DB Executor thread finished");
-    tracing::info!("Shutdown complete!");
-
     Ok(())
 }
 
-async fn message_loop<SF>(mut stream: ClientStream, bot: Bot<SF>) -> anyhow::Result<()>
-where
-    SF: Fn(String, String) -> anyhow::Result<()> + Send + Sync + 'static,
-{
-    let (cancelled_send, mut cancelled_recv) = mpsc::channel::<()>(1);
-    while let Some(message) = stream.next().await.transpose()? {
-        if let Command::PRIVMSG(origin, content) = message.command {
-            if origin.is_channel_name() {
-                if let Some(author) = message.prefix.and_then(|p| match p {
-                    Prefix::Nickname(name, _, _) => Some(name),
-                    Prefix::ServerName(_) => None,
-                }) {
-                    let cancelled_send = cancelled_send.clone();
-                    bot.handle_message(origin, author, content, cancelled_send)
-                        .await;
-                } else {
-                    tracing::warn!("Couldn't get the author for a message");
-                }
-            }
-        }
+fn import_log(
+    cfg: &UberConfig,
+    db: &mut DbMaintenance,
+    path: &Path,
+    channel: Option<String>,
+) -> anyhow::Result<()> {
+    // quotes are stored with network-qualified channels
+    let channel = match (
+        channel.as_deref().map(split_target),
+        cfg.networks().as_slice(),
+    ) {
+        (Some((None, name)), [(network, _)]) => Some(format!("{network}/{name}")),
+        (Some((None, _)), _) => anyhow::bail!(
+            "The channel has to be qualified with a network name (e.g. 'network/#channel')"
+        ),
+        _ => channel,
+    };
+    let file = File::open(path).with_context(|| format!("Couldn't open '{}'", path.display()))?;
+    let LogQuotes {
+        mut quotes,
+        skipped,
+    } = read_log_quotes(
+        BufReader::new(file),
+        date_from_path(path),
+        channel.as_deref(),
+    )
+    .with_context(|| format!("Couldn't read the log '{}'", path.display()))?;
+    // users who opted out don't want to be quoted, even from old logs
+    let opted_out = db.opted_out()?;
+    let total = quotes.len();
+    quotes.retain(|q| {
+        let speakers = if q.lines.is_empty() {
+            vec![q.author.as_str()]
+        } else {
+            q.lines.iter().map(|l| l.speaker.as_str()).collect()
+        };
+        !speakers
+            .iter()
+            .any(|s| opted_out.iter().any(|o| o.eq_ignore_ascii_case(s)))
+    });
+    let opted_out = total - quotes.len();
+    let (added, duplicates) = db.import(quotes)?;
+    println!(
+        "Imported {added} quotes from '{}', skipped {duplicates} duplicates",
+        path.display()
+    );
+    if opted_out > 0 {
+        println!("Skipped {opted_out} quotes of users who opted out");
+    }
+    if skipped > 0 {
+        println!("Ignored {skipped} lines that aren't messages");
     }
-    drop(cancelled_send);
-    let _ = cancelled_recv.recv().await;
     Ok(())
 }
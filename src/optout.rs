@@ -0,0 +1,37 @@
+use std::{collections::HashSet, sync::RwLock};
+
+/// Nicks of users who opted out, shared by the bots of all networks.
+/// Mirrors the database, so messages can be checked without waiting for it.
+#[derive(Default)]
+pub struct OptOutList {
+    nicks: RwLock<HashSet<String>>,
+}
+
+impl OptOutList {
+    pub fn new(nicks: impl IntoIterator<Item = String>) -> Self {
+        Self {
+            nicks: RwLock::new(nicks.into_iter().map(|n| n.to_ascii_lowercase()).collect()),
+        }
+    }
+
+    pub fn contains(&self, nick: &str) -> bool {
+        self.nicks
+            .read()
+            .unwrap()
+            .contains(&nick.to_ascii_lowercase())
+    }
+
+    pub fn insert(&self, nick: &str) {
+        self.nicks
+            .write()
+            .unwrap()
+            .insert(nick.to_ascii_lowercase());
+    }
+
+    pub fn remove(&self, nick: &str) {
+        self.nicks
+            .write()
+            .unwrap()
+            .remove(&nick.to_ascii_lowercase());
+    }
+}
@@ -0,0 +1,30 @@
+use crate::config::RateLimit;
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+/// Tracks recent replies per channel to enforce [`RateLimit`]s
+#[derive(Default)]
+pub struct RateLimiter {
+    recent: Mutex<HashMap<String, VecDeque<Instant>>>,
+}
+
+impl RateLimiter {
+    /// Records a reply in `channel`, returns `false` if the limit was already reached
+    pub fn try_acquire(&self, channel: &str, limit: &RateLimit) -> bool {
+        let now = Instant::now();
+        let period = Duration::from_secs(limit.period);
+        let mut recent = self.recent.lock().unwrap();
+        let replies = recent.entry(channel.to_ascii_lowercase()).or_default();
+        while matches!(replies.front(), Some(t) if now.duration_since(*t) >= period) {
+            replies.pop_front();
+        }
+        if replies.len() >= limit.messages {
+            return false;
+        }
+        replies.push_back(now);
+        true
+    }
+}
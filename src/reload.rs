@@ -0,0 +1,181 @@
+use crate::{bot::BotSettings, config::UberConfig, transport::Router};
+use std::{collections::HashMap, path::PathBuf, sync::Arc};
+use tokio::sync::watch;
+
+/// Waits for SIGHUP, which asks the bot to reload its config
+#[cfg(unix)]
+pub struct ReloadSignal(tokio::signal::unix::Signal);
+
+#[cfg(unix)]
+impl ReloadSignal {
+    pub fn new() -> std::io::Result<Self> {
+        use tokio::signal::unix::{signal, SignalKind};
+        Ok(Self(signal(SignalKind::hangup())?))
+    }
+
+    pub async fn recv(&mut self) {
+        self.0.recv().await;
+    }
+}
+
+/// There's no SIGHUP on Windows, so the config is never reloaded
+#[cfg(windows)]
+pub struct ReloadSignal;
+
+#[cfg(windows)]
+impl ReloadSignal {
+    pub fn new() -> std::io::Result<Self> {
+        Ok(Self)
+    }
+
+    pub async fn recv(&mut self) {
+        std::future::pending::<()>().await;
+    }
+}
+
+/// Applies changes from the config file to the running bot
+pub struct Reloader {
+    path: PathBuf,
+    /// Config the bot was started with, used to detect changes that require a restart
+    initial: UberConfig,
+    /// Network name -> channels the bot is currently in
+    channels: HashMap<String, Vec<String>>,
+    settings: HashMap<String, watch::Sender<Arc<BotSettings>>>,
+    webhooks: Option<watch::Sender<HashMap<String, String>>>,
+    router: Arc<Router>,
+}
+
+impl Reloader {
+    pub fn new(
+        path: PathBuf,
+        initial: UberConfig,
+        settings: HashMap<String, watch::Sender<Arc<BotSettings>>>,
+        webhooks: Option<watch::Sender<HashMap<String, String>>>,
+        router: Arc<Router>,
+    ) -> Self {
+        let channels = initial
+            .networks()
+            .into_iter()
+            .map(|(name, network)| (name.to_string(), network.channels.clone()))
+            .collect();
+        Self {
+            path,
+            initial,
+            channels,
+            settings,
+            webhooks,
+            router,
+        }
+    }
+
+    /// Re-reads the config file and applies it. Errors are logged, and the old config is kept.
+    pub fn reload(&mut self) {
+        tracing::info!("Reloading config from '{}'", self.path.display());
+        let cfg = match UberConfig::load(&self.path).and_then(|cfg| {
+            cfg.validate()?;
+            Ok(cfg)
+        }) {
+            Ok(cfg) => cfg,
+            Err(e) => {
+                tracing::error!("Couldn't reload the config, keeping the old one: {}", e);
+                return;
+            }
+        };
+        self.apply_networks(&cfg);
+        self.apply_web(&cfg);
+        self.check_restart_required(&cfg);
+        tracing::info!("Config reloaded");
+    }
+
+    fn apply_networks(&mut self, cfg: &UberConfig) {
+        for (name, network) in cfg.networks() {
+            let (Some(settings), Some(channels), Some(transport)) = (
+                self.settings.get(name),
+                self.channels.get_mut(name),
+                self.router.get(name),
+            ) else {
+                tracing::warn!("Network '{}' was added, restart required", name);
+                continue;
+            };
+            let keys = cfg.channel_keys(name);
+            for channel in network.channels.iter().filter(|c| !channels.contains(c)) {
+                tracing::info!("Joining {} on network '{}'", channel, name);
+                let key = keys.get(&channel.to_ascii_lowercase());
+                if let Err(e) = transport.join(channel, key.map(String::as_str)) {
+                    tracing::error!("Couldn't join {}: {}", channel, e);
+                }
+            }
+            for channel in channels.iter().filter(|c| !network.channels.contains(c)) {
+                tracing::info!("Leaving {} on network '{}'", channel, name);
+                if let Err(e) = transport.part(channel) {
+                    tracing::error!("Couldn't leave {}: {}", channel, e);
+                }
+            }
+            channels.clone_from(&network.channels);
+            let _ = settings.send(Arc::new(BotSettings::new(cfg, name, network)));
+        }
+    }
+
+    fn apply_web(&self, cfg: &UberConfig) {
+        let webhooks = cfg.web.as_ref().map(|w| w.webhooks.clone());
+        if let (Some(tx), Some(webhooks)) = (&self.webhooks, webhooks) {
+            let _ = tx.send(webhooks);
+        }
+    }
+
+    /// Logs changes that can't be applied while the bot is running
+    fn check_restart_required(&self, cfg: &UberConfig) {
+        let mut changed = Vec::new();
+        let initial_networks = self.initial.networks();
+        let networks = cfg.networks();
+        for (name, network) in &initial_networks {
+            match networks.iter().find(|(n, _)| n == name) {
+                Some((_, new)) if !network.same_connection(new) => {
+                    changed.push(format!("connection settings of network '{name}'"));
+                }
+                None => changed.push(format!("removed network '{name}'")),
+                _ => {}
+            }
+        }
+        let (old, new) = (&self.initial.bot, &cfg.bot);
+        if old.db_path != new.db_path {
+            changed.push("bot.db_path".into());
+        }
+        if old.history_depth != new.history_depth {
+            changed.push("bot.history_depth".into());
+        }
+        if old.search_limit != new.search_limit {
+            changed.push("bot.search_limit".into());
+        }
+        if old.user_agent != new.user_agent {
+            changed.push("bot.user_agent".into());
+        }
+        if old.report_cooldown != new.report_cooldown {
+            changed.push("bot.report_cooldown".into());
+        }
+        if old.weighted_quotes != new.weighted_quotes {
+            changed.push("bot.weighted_quotes".into());
+        }
+        if old.ungrab_window != new.ungrab_window {
+            changed.push("bot.ungrab_window".into());
+        }
+        if self.initial.log_level != cfg.log_level {
+            changed.push("log_level".into());
+        }
+        if self.initial.spotify != cfg.spotify {
+            changed.push("[spotify]".into());
+        }
+        if self.initial.web.as_ref().map(|w| w.listen) != cfg.web.as_ref().map(|w| w.listen) {
+            changed.push("[web]".into());
+        }
+        if self.initial.qotd != cfg.qotd {
+            changed.push("[qotd]".into());
+        }
+        if !changed.is_empty() {
+            tracing::warn!(
+                "Some changes will only be applied after a restart: {}",
+                changed.join(", ")
+            );
+        }
+    }
+}
@@ -0,0 +1,104 @@
+use crate::{
+    formatting::strip_formatting,
+    transport::{ChannelMessage, MessageStream, Transport},
+};
+use futures_util::stream;
+use std::{io::BufRead, thread};
+use tokio::{
+    select,
+    sync::{mpsc, watch},
+};
+
+/// Prints the bot's messages to stdout, with formatting codes removed
+pub struct ConsoleTransport {
+    nick: String,
+    shutdown: watch::Sender<bool>,
+}
+
+impl Transport for ConsoleTransport {
+    fn send_message(&self, target: String, msg: String) -> anyhow::Result<()> {
+        for line in msg.split("\r\n") {
+            println!("[{target}] <{}> {}", self.nick, strip_formatting(line));
+        }
+        Ok(())
+    }
+
+    fn join(&self, channel: &str, _key: Option<&str>) -> anyhow::Result<()> {
+        println!("* {} has joined {channel}", self.nick);
+        Ok(())
+    }
+
+    fn part(&self, channel: &str) -> anyhow::Result<()> {
+        println!("* {} has left {channel}", self.nick);
+        Ok(())
+    }
+
+    fn quit(&self, reason: &str) -> anyhow::Result<()> {
+        println!("* {} has quit ({reason})", self.nick);
+        let _ = self.shutdown.send(true);
+        Ok(())
+    }
+}
+
+struct ConsoleState {
+    lines: mpsc::UnboundedReceiver<std::io::Result<String>>,
+    shutdown: watch::Receiver<bool>,
+    nick: String,
+    channel: String,
+}
+
+/// Reads messages from stdin, as if they were sent by `nick` in `channel`.
+/// Lines starting with `/nick` or `/join` change the current nick or channel.
+pub fn start(bot_nick: String, nick: String, channel: String) -> (ConsoleTransport, MessageStream) {
+    let (line_tx, lines) = mpsc::unbounded_channel();
+    // stdin reads can't be cancelled, so we use a detached thread instead of spawn_blocking,
+    // which would prevent the runtime from shutting down until a line is entered
+    thread::spawn(move || {
+        for line in std::io::stdin().lock().lines() {
+            if line_tx.send(line).is_err() {
+                break;
+            }
+        }
+    });
+    let (shutdown_tx, shutdown) = watch::channel(false);
+    println!("Console mode: you are {nick} in {channel}. Use /nick <nick> and /join <channel> to switch.");
+    let state = ConsoleState {
+        lines,
+        shutdown,
+        nick,
+        channel,
+    };
+    let messages = stream::unfold(state, |mut state| async move {
+        loop {
+            let line = select! {
+                _ = state.shutdown.changed() => return None,
+                line = state.lines.recv() => line?,
+            };
+            let line = match line {
+                Ok(line) => line,
+                Err(e) => return Some((Err(e.into()), state)),
+            };
+            if let Some(nick) = line.strip_prefix("/nick ") {
+                state.nick = nick.trim().to_string();
+                println!("* You are now known as {}", state.nick);
+            } else if let Some(channel) = line.strip_prefix("/join ") {
+                state.channel = channel.trim().to_string();
+                println!("* Now talking in {}", state.channel);
+            } else if !line.trim().is_empty() {
+                let msg = ChannelMessage {
+                    origin: state.channel.clone(),
+                    author: state.nick.clone(),
+                    content: line,
+                };
+                return Some((Ok(msg), state));
+            }
+        }
+    });
+    (
+        ConsoleTransport {
+            nick: bot_nick,
+            shutdown: shutdown_tx,
+        },
+        Box::pin(messages),
+    )
+}
@@ -0,0 +1,121 @@
+use crate::{
+    config::IrcConfig,
+    transport::{ChannelMessage, MessageStream, Transport},
+};
+use futures_util::{future, stream::StreamExt};
+use irc::{
+    client::{prelude::Config, Client},
+    proto::{Capability, ChannelExt, Command, Message, Prefix},
+};
+use std::{collections::HashMap, hash::BuildHasher};
+
+pub struct IrcTransport {
+    client: Client,
+}
+
+impl Transport for IrcTransport {
+    fn send_message(&self, target: String, msg: String) -> anyhow::Result<()> {
+        Ok(self.client.send_privmsg(target, msg)?)
+    }
+
+    fn join(&self, channel: &str, key: Option<&str>) -> anyhow::Result<()> {
+        let key = key.map(ToString::to_string);
+        Ok(self.client.send(Command::JOIN(channel.into(), key, None))?)
+    }
+
+    fn part(&self, channel: &str) -> anyhow::Result<()> {
+        Ok(self.client.send_part(channel)?)
+    }
+
+    fn quit(&self, reason: &str) -> anyhow::Result<()> {
+        Ok(self.client.send_quit(reason)?)
+    }
+}
+
+/// Connects to the IRC server and identifies, using SASL PLAIN if a password is configured.
+/// `keys` maps lowercase channel names to their keys.
+pub async fn connect<S: BuildHasher>(
+    cfg: &IrcConfig,
+    keys: &HashMap<String, String, S>,
+) -> anyhow::Result<(IrcTransport, MessageStream)> {
+    let uber_ver = concat!("Überbot ", env!("CARGO_PKG_VERSION"));
+    let nick = cfg.nickname.clone().unwrap_or_else(|| cfg.username.clone());
+    let irc_config = Config {
+        alt_nicks: cfg
+            .alt_nicks
+            .clone()
+            .unwrap_or_else(|| vec![format!("{}_", &nick), format!("{}__", &nick)]),
+        nickname: Some(nick),
+        username: Some(cfg.username.clone()),
+        realname: Some(cfg.username.clone()),
+        server: Some(cfg.host.clone()),
+        port: Some(cfg.port),
+        use_tls: Some(cfg.tls),
+        channels: cfg.channels.clone(),
+        channel_keys: cfg
+            .channels
+            .iter()
+            .filter_map(|c| Some((c.clone(), keys.get(&c.to_ascii_lowercase())?.clone())))
+            .collect(),
+        umodes: cfg.mode.clone(),
+        user_info: Some(uber_ver.into()),
+        version: Some(uber_ver.into()),
+        ..Config::default()
+    };
+    let mut client = Client::from_config(irc_config).await?;
+    let stream = client.stream()?;
+
+    if let Some(pass) = &cfg.sasl_pass {
+        let sasl_pass = base64::encode(format!(
+            "{}\0{}\0{}",
+            &cfg.username,
+            &cfg.username,
+            pass.expose()
+        ));
+
+        client.send_cap_req(&[Capability::Sasl])?;
+        client.send(Command::NICK(client.current_nickname().into()))?;
+        client.send(Command::USER(
+            cfg.username.clone(),
+            "0".into(),
+            cfg.username.clone(),
+        ))?;
+        client.send_sasl_plain()?;
+        client.send_sasl(sasl_pass)?;
+        client.send(Command::CAP(
+            None,
+            irc::proto::CapSubCommand::END,
+            None,
+            None,
+        ))?;
+    } else {
+        client.identify()?;
+    }
+
+    let messages = stream.filter_map(|message| {
+        future::ready(match message {
+            Ok(message) => channel_message(message).map(Ok),
+            Err(e) => Some(Err(e.into())),
+        })
+    });
+    Ok((IrcTransport { client }, Box::pin(messages)))
+}
+
+fn channel_message(message: Message) -> Option<ChannelMessage> {
+    if let Command::PRIVMSG(origin, content) = message.command {
+        if origin.is_channel_name() {
+            if let Some(author) = message.prefix.and_then(|p| match p {
+                Prefix::Nickname(name, _, _) => Some(name),
+                Prefix::ServerName(_) => None,
+            }) {
+                return Some(ChannelMessage {
+                    origin,
+                    author,
+                    content,
+                });
+            }
+            tracing::warn!("Couldn't get the author for a message");
+        }
+    }
+    None
+}
@@ -0,0 +1,100 @@
+use crate::bot::Bot;
+use futures_util::stream::{Stream, StreamExt};
+use std::{pin::Pin, sync::Arc};
+use tokio::sync::mpsc;
+
+pub mod console;
+pub mod irc;
+
+/// A message sent to one of the channels the bot is in
+pub struct ChannelMessage {
+    pub origin: String,
+    pub author: String,
+    pub content: String,
+}
+
+pub type MessageStream = Pin<Box<dyn Stream<Item = anyhow::Result<ChannelMessage>> + Send>>;
+
+/// A chat connection the bot sends its messages through.
+/// Incoming messages are delivered by the [`MessageStream`] created along with the transport.
+pub trait Transport: Send + Sync + 'static {
+    /// Sends a message to a channel or user
+    fn send_message(&self, target: String, msg: String) -> anyhow::Result<()>;
+    fn join(&self, channel: &str, key: Option<&str>) -> anyhow::Result<()>;
+    fn part(&self, channel: &str) -> anyhow::Result<()>;
+    /// Starts disconnecting, the message stream ends once the connection is closed
+    fn quit(&self, reason: &str) -> anyhow::Result<()>;
+}
+
+/// Splits a message target into the network name and the channel or nick.
+/// Targets can be qualified with a network name, e.g. `libera/#uberbot`.
+pub fn split_target(target: &str) -> (Option<&str>, &str) {
+    match target.split_once('/') {
+        Some((network, target)) if !network.is_empty() && !network.starts_with(['#', '&']) => {
+            (Some(network), target)
+        }
+        _ => (None, target),
+    }
+}
+
+/// Delivers messages to the transports of all connected networks
+pub struct Router {
+    transports: Vec<(String, Arc<dyn Transport>)>,
+}
+
+impl Router {
+    pub fn new(transports: Vec<(String, Arc<dyn Transport>)>) -> Self {
+        Self { transports }
+    }
+
+    /// Sends a message to `target`, which can be qualified with a network name.
+    /// Unqualified targets are sent to `network`, or to the only network if it's `None`.
+    pub fn send(&self, network: Option<&str>, target: &str, msg: String) -> anyhow::Result<()> {
+        let (network, target) = match split_target(target) {
+            (Some(network), target) => (Some(network), target),
+            (None, target) => (network, target),
+        };
+        let transport = match network {
+            Some(network) => self
+                .get(network)
+                .ok_or_else(|| anyhow::anyhow!("Unknown network '{network}'"))?,
+            None if self.transports.len() == 1 => self.transports[0].1.as_ref(),
+            None => anyhow::bail!("Target '{target}' has to be qualified with a network name"),
+        };
+        transport.send_message(target.to_string(), msg)
+    }
+
+    /// Returns the transport of a network
+    pub fn get(&self, network: &str) -> Option<&dyn Transport> {
+        self.transports
+            .iter()
+            .find(|(n, _)| n == network)
+            .map(|(_, t)| t.as_ref())
+    }
+
+    /// Disconnects from all networks
+    pub fn quit(&self, reason: &str) {
+        for (network, transport) in &self.transports {
+            if let Err(e) = transport.quit(reason) {
+                tracing::warn!("Couldn't send QUIT to network '{}': {}", network, e);
+            }
+        }
+    }
+}
+
+/// Feeds incoming messages into the bot until the stream ends,
+/// then waits for all running handlers to finish
+pub async fn message_loop<SF>(mut messages: MessageStream, bot: Bot<SF>) -> anyhow::Result<()>
+where
+    SF: Fn(String, String) -> anyhow::Result<()> + Send + Sync + 'static,
+{
+    let (cancelled_send, mut cancelled_recv) = mpsc::channel::<()>(1);
+    while let Some(msg) = messages.next().await.transpose()? {
+        let cancelled_send = cancelled_send.clone();
+        bot.handle_message(msg.origin, msg.author, msg.content, cancelled_send)
+            .await;
+    }
+    drop(cancelled_send);
+    let _ = cancelled_recv.recv().await;
+    Ok(())
+}
@@ -1,4 +1,4 @@
-use std::{convert::Infallible, sync::Arc};
+use std::{collections::HashMap, convert::Infallible, net::SocketAddr, sync::Arc};
 
 use hyper::{
     body::to_bytes,
@@ -6,9 +6,7 @@ use hyper::{
     service::{make_service_fn, service_fn},
     Body, Request, Response, Server, StatusCode,
 };
-use tokio::sync::broadcast;
-
-use crate::config::HttpConfig;
+use tokio::sync::{broadcast, watch};
 
 mod parser;
 
@@ -16,7 +14,9 @@ pub struct HttpContext<SF>
 where
     SF: Fn(String, String) -> anyhow::Result<()>,
 {
-    pub cfg: HttpConfig,
+    pub listen: SocketAddr,
+    /// Webhook path -> target channel, can be updated while the service is running
+    pub webhooks: watch::Receiver<HashMap<String, String>>,
     pub sendmsg: SF,
 }
 
@@ -41,7 +41,8 @@ where
             .body(Body::from("no content-type"))?);
     }
     let webhook = (req.uri().path()[1..]).to_string();
-    let Some(channel) = ctx.cfg.webhooks.get(&webhook) else {
+    let channel = ctx.webhooks.borrow().get(&webhook).cloned();
+    let Some(channel) = channel else {
         return Ok(Response::builder()
             .status(StatusCode::NOT_FOUND)
             .body(Body::from("webhook path not registered"))?);
@@ -50,7 +51,7 @@ where
     let body = String::from_utf8_lossy(&body_bytes);
     tracing::debug!("received request: {}", body);
     let response = parser::textify(&body, &webhook)?;
-    (ctx.sendmsg)(channel.to_string(), response)?;
+    (ctx.sendmsg)(channel, response)?;
     let resp = Response::builder()
         .status(StatusCode::OK)
         .body(Body::empty())?;
@@ -74,7 +75,7 @@ where
         }
     });
 
-    let server = Server::bind(&ctx.cfg.listen).serve(make_service);
+    let server = Server::bind(&ctx.listen).serve(make_service);
     server
         .with_graceful_shutdown(async {
             shutdown.recv().await.unwrap();
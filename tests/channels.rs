@@ -0,0 +1,264 @@
+mod common;
+
+use common::{TempDb, TestBot, BASE_CONFIG};
+use uberbot::{async_trait, Command, CommandContext};
+
+struct Colorful;
+
+#[async_trait]
+impl Command for Colorful {
+    async fn execute(&self, ctx: CommandContext) -> anyhow::Result<String> {
+        let language = ctx.language.as_deref().unwrap_or("none");
+        Ok(format!("\x0304red\x0f {language}"))
+    }
+}
+
+async fn start(channels: &str) -> TestBot {
+    let config = format!(
+        "{}\n{channels}",
+        BASE_CONFIG.replace(
+            r##"channels = ["#test"]"##,
+            r##"channels = ["#test", "#other"]"##
+        )
+    );
+    TestBot::start_with(&config, TempDb::new(), |b| {
+        b.add_command("color", Colorful);
+    })
+    .await
+}
+
+#[tokio::test]
+async fn channels_are_joined_with_keys() {
+    let mut bot = start(
+        r##"
+[channels."#other"]
+key = "sekrit"
+"##,
+    )
+    .await;
+    assert_eq!(bot.conn.expect_line("JOIN ").await, "JOIN #other sekrit");
+}
+
+#[tokio::test]
+async fn channels_have_their_own_prefixes() {
+    let mut bot = start(
+        r##"
+[channels."#other"]
+prefixes = ["o!"]
+"##,
+    )
+    .await;
+    bot.conn.say("alice", "#other", "u!nope").await;
+    bot.conn.say("alice", "#other", "o!nope").await;
+    assert_eq!(bot.conn.expect_reply("#other").await, "Unknown command.");
+    assert_eq!(bot.ask("alice", "u!nope").await, "Unknown command.");
+}
+
+#[tokio::test]
+async fn commands_and_triggers_can_be_disabled() {
+    let mut bot = start(
+        r##"
+[channels."#test"]
+disabled_commands = ["owo"]
+disabled_triggers = ["sed"]
+
+[channels."#other"]
+commands = ["color"]
+"##,
+    )
+    .await;
+    assert_eq!(bot.ask("alice", "u!owo").await, "Unknown command.");
+    bot.conn.say("alice", "#test", "hello").await;
+    bot.conn.say("alice", "#test", "s/hello/bye/").await;
+    bot.conn.say("alice", "#other", "u!mock").await;
+    assert_eq!(bot.conn.expect_privmsg().await.1, "Unknown command.");
+    bot.conn.say("alice", "#other", "u!color").await;
+    assert_eq!(bot.conn.expect_privmsg().await.1, "\x0304red\x0f none");
+}
+
+#[tokio::test]
+async fn color_and_language() {
+    let mut bot = start(
+        r##"
+[channels."#test"]
+color = false
+language = "de"
+"##,
+    )
+    .await;
+    assert_eq!(bot.ask("alice", "u!color").await, "red de");
+    bot.conn.say("alice", "#other", "u!color").await;
+    assert_eq!(bot.conn.expect_reply("#other").await, "\x0304red\x0f none");
+}
+
+#[tokio::test]
+async fn rate_limit() {
+    let mut bot = start(
+        r##"
+[channels."#test"]
+rate_limit = { messages = 2, period = 60 }
+"##,
+    )
+    .await;
+    assert_eq!(bot.ask("alice", "u!nope").await, "Unknown command.");
+    assert_eq!(bot.ask("alice", "u!color").await, "\x0304red\x0f none");
+    assert_eq!(bot.ask("alice", "u!color").await, "\x0304red\x0f none");
+    // the limit only applies to #test
+    bot.conn.say("alice", "#test", "u!color").await;
+    bot.conn.say("alice", "#other", "u!color").await;
+    assert_eq!(bot.conn.expect_privmsg().await.0, "#other");
+}
+
+#[tokio::test]
+async fn quote_namespaces() {
+    let mut bot = start(
+        r##"
+[channels."#other"]
+quote_namespace = "offtopic"
+[channels."#third"]
+quote_namespace = "offtopic"
+"##,
+    )
+    .await;
+    bot.conn.say("alice", "#test", "work stuff").await;
+    bot.ask("bob", "u!grab alice").await;
+    bot.conn.say("alice", "#other", "funny stuff").await;
+    bot.conn.say("bob", "#other", "u!grab alice").await;
+    assert_eq!(
+        bot.conn.expect_reply("#other").await,
+        "Quote #2 added (1 messages)."
+    );
+    for _ in 0..3 {
+        assert_eq!(
+            bot.ask("bob", "u!quot alice").await,
+            "\"work stuff\" ~alice (#1)"
+        );
+    }
+    assert_eq!(
+        bot.ask("bob", "u!qsearch stuff").await,
+        "\"work \x02stuff\x02\" ~alice (#1)"
+    );
+    // channels in the same namespace share their quotes
+    bot.conn.say("bob", "#third", "u!quot alice").await;
+    assert_eq!(
+        bot.conn.expect_reply("#third").await,
+        "\"funny stuff\" ~alice (#2)"
+    );
+    assert_eq!(
+        bot.ask("bob", "u!quot -c #other alice").await,
+        "\"funny stuff\" ~alice (#2)"
+    );
+}
+
+#[tokio::test]
+async fn quote_ids_and_searches_are_scoped() {
+    let mut bot = start(
+        r##"
+[channels."#other"]
+quote_namespace = "offtopic"
+"##,
+    )
+    .await;
+    bot.conn.say("alice", "#other", "funny stuff").await;
+    bot.conn.say("bob", "#other", "u!grab alice").await;
+    bot.conn.expect_reply("#other").await;
+    for cmd in ["quote 1", "+1 1", "unquote 1"] {
+        assert_eq!(
+            bot.ask("bob", &format!("u!{cmd}")).await,
+            "Quote #1 doesn't exist."
+        );
+    }
+    bot.conn.say("bob", "#other", "u!quote 1").await;
+    assert_eq!(
+        bot.conn.expect_reply("#other").await,
+        "\"funny stuff\" ~alice (#1)"
+    );
+    // search pages are only shown in the channel the search was started in
+    bot.conn.say("bob", "#other", "u!qsearch stuff").await;
+    bot.conn.expect_reply("#other").await;
+    assert_eq!(
+        bot.ask("bob", "u!qpage 1").await,
+        "You need to initiate a search first using 'qsearch'."
+    );
+}
+
+#[tokio::test]
+async fn grabs_only_use_the_channel_history() {
+    let mut bot = start("").await;
+    bot.conn.say("alice", "#test", "work stuff").await;
+    bot.conn.say("alice", "#other", "funny stuff").await;
+    assert_eq!(
+        bot.ask("bob", "u!grab alice").await,
+        "Quote #1 added (1 messages)."
+    );
+    assert_eq!(
+        bot.ask("bob", "u!quote 1").await,
+        "\"work stuff\" ~alice (#1)"
+    );
+    bot.conn.say("bob", "#other", "u!grab 2 alice").await;
+    assert_eq!(
+        bot.conn.expect_reply("#other").await,
+        "Quote #2 added (1 messages)."
+    );
+}
+
+#[tokio::test]
+async fn duplicates_are_checked_per_namespace() {
+    let mut bot = start("").await;
+    bot.conn.say("alice", "#test", "hello").await;
+    bot.conn.say("alice", "#other", "hello").await;
+    assert_eq!(
+        bot.ask("bob", "u!grab alice").await,
+        "Quote #1 added (1 messages)."
+    );
+    bot.conn.say("bob", "#other", "u!grab alice").await;
+    assert_eq!(
+        bot.conn.expect_reply("#other").await,
+        "Quote #2 added (1 messages)."
+    );
+    bot.conn.say("carol", "#other", "u!grab alice").await;
+    assert_eq!(
+        bot.conn.expect_reply("#other").await,
+        "This was already quoted as #2."
+    );
+}
+
+#[tokio::test]
+async fn ungrab_only_removes_grabs_of_the_channel() {
+    let mut bot = start("").await;
+    bot.conn.say("alice", "#test", "hello").await;
+    bot.ask("bob", "u!grab alice").await;
+    bot.conn.say("bob", "#other", "u!ungrab").await;
+    assert_eq!(
+        bot.conn.expect_reply("#other").await,
+        "You haven't grabbed anything recently."
+    );
+    assert_eq!(
+        bot.ask("bob", "u!ungrab").await,
+        "Removed \"hello\" ~alice (#1)"
+    );
+}
+
+#[tokio::test]
+async fn quote_stats_are_scoped() {
+    let mut bot = start("").await;
+    bot.conn.say("alice", "#test", "hello").await;
+    bot.ask("bob", "u!grab alice").await;
+    bot.conn.say("carol", "#other", "hi").await;
+    bot.conn.say("dave", "#other", "u!grab carol").await;
+    bot.conn.expect_reply("#other").await;
+    assert_eq!(
+        bot.ask("bob", "u!qstats").await,
+        "1 quotes. Most quoted: alice (1). Top grabbers: bob (1)."
+    );
+    bot.conn.say("dave", "#other", "u!qstats alice").await;
+    assert_eq!(
+        bot.conn.expect_reply("#other").await,
+        "alice has 0 quotes and grabbed 0 quotes."
+    );
+    bot.conn.say("dave", "#other", "u!qstats dave").await;
+    assert_eq!(
+        bot.conn.expect_reply("#other").await,
+        "dave has 0 quotes and grabbed 1 quotes."
+    );
+}
@@ -0,0 +1,122 @@
+mod common;
+
+use common::TestBot;
+
+#[tokio::test]
+async fn unknown_command() {
+    let mut bot = TestBot::start().await;
+    assert_eq!(bot.ask("alice", "u!nope").await, "Unknown command.");
+}
+
+#[tokio::test]
+async fn help_is_split_into_lines() {
+    let mut bot = TestBot::start().await;
+    let first = bot.ask("alice", "u!help").await;
+    assert!(first.contains("Überbot"));
+    let second = bot.conn.expect_reply("#test").await;
+    assert!(second.contains("grab [count] <user>"));
+}
+
+#[tokio::test]
+async fn help_only_lists_compiled_features() {
+    let mut bot = TestBot::start().await;
+    let mut help = bot.ask("alice", "u!help").await;
+    let mut lines = 1;
+    while !help.contains("This bot can also resolve") {
+        let line = bot.conn.expect_reply("#test").await;
+        assert!(line.len() <= 90, "{line:?} is too long");
+        help += &line;
+        lines += 1;
+    }
+    assert!(lines <= 7);
+    assert_eq!(help.contains("waifu <category>"), cfg!(feature = "waifu"));
+    assert_eq!(
+        help.contains("ev <math expression>"),
+        cfg!(feature = "eval")
+    );
+    assert_eq!(help.contains("HTML titles"), cfg!(feature = "title"));
+    assert_eq!(help.contains("Spotify links"), cfg!(feature = "spotify"));
+    assert!(help.contains("sed expressions"));
+}
+
+#[tokio::test]
+async fn sed_edits_last_message() {
+    let mut bot = TestBot::start().await;
+    bot.conn.say("alice", "#test", "hello world").await;
+    assert_eq!(
+        bot.ask("alice", "s/world/there/").await,
+        "<alice> hello there"
+    );
+    // the edit is saved in the history
+    assert_eq!(bot.ask("alice", "s/hello/hi/").await, "<alice> hi there");
+}
+
+#[tokio::test]
+async fn sed_edits_are_grabbed() {
+    let mut bot = TestBot::start().await;
+    bot.conn.say("alice", "#test", "helo world").await;
+    bot.ask("alice", "s/helo/hello/").await;
+    bot.ask("bob", "u!convo alice").await;
+    assert_eq!(
+        bot.ask("bob", "u!quote 1").await,
+        "<alice> hello world (#1)"
+    );
+    bot.ask("bob", "u!grab alice").await;
+    assert_eq!(
+        bot.ask("bob", "u!quote 2").await,
+        "\"hello world\" ~alice (#2)"
+    );
+}
+
+#[tokio::test]
+async fn sed_foreign_message() {
+    let mut bot = TestBot::start().await;
+    bot.conn.say("alice", "#test", "I like cats").await;
+    assert_eq!(
+        bot.ask("bob", "alice: s/cats/dogs/g").await,
+        "(edited by bob) <alice> I like dogs"
+    );
+    assert_eq!(
+        bot.ask("bob", "alice: s/like/hate/").await,
+        "(edited by bob) <alice> I hate cats"
+    );
+}
+
+#[tokio::test]
+async fn sed_without_history() {
+    let mut bot = TestBot::start().await;
+    assert_eq!(
+        bot.ask("alice", "s/a/b/").await,
+        "No previous messages found."
+    );
+}
+
+#[tokio::test]
+async fn sed_invalid_regex() {
+    let mut bot = TestBot::start().await;
+    bot.conn.say("alice", "#test", "hello").await;
+    assert_eq!(
+        bot.ask("alice", "s/(/b/").await,
+        "Invalid regular expression."
+    );
+}
+
+#[tokio::test]
+async fn leet() {
+    let mut bot = TestBot::start().await;
+    bot.conn.say("alice", "#test", "leet speak").await;
+    assert_eq!(bot.ask("bob", "u!leet alice").await, "l337 5p34k");
+}
+
+#[cfg(feature = "eval")]
+#[tokio::test]
+async fn eval_remembers_last_result() {
+    let mut bot = TestBot::start().await;
+    assert_eq!(bot.ask("alice", "u!ev 2*3").await, "2*3 = 6.0000000000");
+    assert_eq!(bot.ask("alice", "u!ev x+1").await, "x+1 = 7.0000000000");
+    assert_eq!(bot.ask("bob", "u!ev x+1").await, "x+1 = 1.0000000000");
+    assert!(bot
+        .ask("alice", "u!ev 2+")
+        .await
+        .starts_with("Invalid expression:"));
+}
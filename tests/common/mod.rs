@@ -0,0 +1,256 @@
+//! In-process IRC server and bot fixtures shared by the integration tests
+#![allow(dead_code)]
+
+use std::{
+    env, fs,
+    path::PathBuf,
+    sync::atomic::{AtomicUsize, Ordering},
+    thread,
+    time::Duration,
+};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader, Lines},
+    net::{
+        tcp::{OwnedReadHalf, OwnedWriteHalf},
+        TcpListener,
+    },
+    sync::oneshot,
+    task::JoinHandle,
+    time::timeout,
+};
+use uberbot::{database::Quote, BotBuilder, DbExecutor, UberConfig};
+
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+pub const BASE_CONFIG: &str = r##"
+[irc]
+host = "127.0.0.1"
+port = 0
+tls = false
+username = "uberbot"
+channels = ["#test"]
+
+[bot]
+prefixes = ["u!"]
+history_depth = 5
+"##;
+
+/// Temporary SQLite database, removed when dropped
+pub struct TempDb {
+    pub path: PathBuf,
+}
+
+impl TempDb {
+    pub fn new() -> Self {
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+        let path = env::temp_dir().join(format!(
+            "uberbot-test-{}-{}.db3",
+            std::process::id(),
+            COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        let _ = fs::remove_file(&path);
+        Self { path }
+    }
+
+    pub fn path_str(&self) -> &str {
+        self.path.to_str().unwrap()
+    }
+
+    /// Inserts quotes through the regular database executor
+    pub async fn seed_quotes(&self, quotes: &[(&str, &str)]) {
+        let (exec, conn) = DbExecutor::create(self.path_str()).unwrap();
+        let exec_thread = thread::spawn(move || exec.run());
+        for (author, quote) in quotes {
+            conn.add_quote(
+                Quote {
+                    author: (*author).to_string(),
+                    quote: (*quote).to_string(),
+                    ..Quote::default()
+                },
+                None,
+            )
+            .await
+            .unwrap();
+        }
+        drop(conn);
+        exec_thread.join().unwrap();
+    }
+}
+
+impl Drop for TempDb {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// Fake IRC server accepting a single client
+pub struct MockServer {
+    listener: TcpListener,
+}
+
+impl MockServer {
+    pub async fn bind() -> Self {
+        Self {
+            listener: TcpListener::bind("127.0.0.1:0").await.unwrap(),
+        }
+    }
+
+    pub fn port(&self) -> u16 {
+        self.listener.local_addr().unwrap().port()
+    }
+
+    /// Accepts the bot's connection and waits until it has registered
+    pub async fn accept(&self) -> MockConnection {
+        let (socket, _) = timeout(TIMEOUT, self.listener.accept())
+            .await
+            .expect("the bot didn't connect")
+            .unwrap();
+        let (read, write) = socket.into_split();
+        let mut conn = MockConnection {
+            lines: BufReader::new(read).lines(),
+            write,
+            registration: Vec::new(),
+        };
+        let (mut cap_end, mut user) = (false, false);
+        while !(cap_end && user) {
+            let line = conn.recv_line().await.expect("connection closed");
+            cap_end |= line.starts_with("CAP END");
+            user |= line.starts_with("USER ");
+            if line == "AUTHENTICATE PLAIN" {
+                conn.send_line("AUTHENTICATE +").await;
+            } else if line.starts_with("AUTHENTICATE ") {
+                conn.send_line(":mock 903 uberbot :SASL authentication successful")
+                    .await;
+            }
+            conn.registration.push(line);
+        }
+        conn.send_line(":mock 001 uberbot :Welcome to the mock network")
+            .await;
+        // the client joins its channels after the end of MOTD
+        conn.send_line(":mock 422 uberbot :MOTD File is missing")
+            .await;
+        conn
+    }
+}
+
+/// Server side of the bot's connection
+pub struct MockConnection {
+    lines: Lines<BufReader<OwnedReadHalf>>,
+    write: OwnedWriteHalf,
+    /// Lines received from the bot before it was welcomed
+    pub registration: Vec<String>,
+}
+
+impl MockConnection {
+    pub async fn send_line(&mut self, line: &str) {
+        self.write
+            .write_all(format!("{line}\r\n").as_bytes())
+            .await
+            .unwrap();
+    }
+
+    /// Returns the next line sent by the bot, or `None` if it closed the connection
+    pub async fn recv_line(&mut self) -> Option<String> {
+        timeout(TIMEOUT, self.lines.next_line())
+            .await
+            .expect("timed out waiting for the bot")
+            .unwrap()
+    }
+
+    /// Waits for a line starting with `prefix`, skipping all others
+    pub async fn expect_line(&mut self, prefix: &str) -> String {
+        loop {
+            let line = self.recv_line().await.expect("connection closed");
+            if line.starts_with(prefix) {
+                return line;
+            }
+        }
+    }
+
+    /// Sends a channel message as `nick`
+    pub async fn say(&mut self, nick: &str, channel: &str, msg: &str) {
+        self.send_line(&format!(":{nick}!{nick}@mock PRIVMSG {channel} :{msg}"))
+            .await;
+    }
+
+    /// Waits for the next message sent by the bot and returns its target and content
+    pub async fn expect_privmsg(&mut self) -> (String, String) {
+        let line = self.expect_line("PRIVMSG ").await;
+        let (target, text) = line["PRIVMSG ".len()..].split_once(' ').unwrap();
+        let text = text.strip_prefix(':').unwrap_or(text);
+        (target.to_string(), text.to_string())
+    }
+
+    /// Waits for the next message sent by the bot to `target`, skipping messages sent elsewhere
+    pub async fn expect_reply(&mut self, target: &str) -> String {
+        loop {
+            let (t, text) = self.expect_privmsg().await;
+            if t == target {
+                return text;
+            }
+        }
+    }
+}
+
+/// A bot connected to a mock server, shut down when dropped
+pub struct TestBot {
+    pub conn: MockConnection,
+    pub db: TempDb,
+    shutdown: Option<oneshot::Sender<()>>,
+    task: Option<JoinHandle<anyhow::Result<()>>>,
+}
+
+impl TestBot {
+    pub async fn start() -> Self {
+        Self::start_with(BASE_CONFIG, TempDb::new(), |_| {}).await
+    }
+
+    /// Starts the bot with the given config and database,
+    /// `setup` can be used to register additional commands
+    pub async fn start_with<F: FnOnce(&mut BotBuilder)>(
+        config: &str,
+        db: TempDb,
+        setup: F,
+    ) -> Self {
+        let server = MockServer::bind().await;
+        let mut cfg: UberConfig = toml::from_str(config).unwrap();
+        cfg.irc.as_mut().unwrap().port = server.port();
+        cfg.bot.db_path = Some(db.path_str().into());
+        let mut builder = BotBuilder::new(cfg);
+        builder.add_builtin_commands().await.unwrap();
+        setup(&mut builder);
+        let (shutdown_tx, shutdown_rx) = oneshot::channel::<()>();
+        let task = tokio::spawn(builder.run_until(async {
+            let _ = shutdown_rx.await;
+        }));
+        let mut conn = server.accept().await;
+        conn.expect_line("JOIN ").await;
+        Self {
+            conn,
+            db,
+            shutdown: Some(shutdown_tx),
+            task: Some(task),
+        }
+    }
+
+    /// Sends a message as `nick` in #test and returns the bot's reply
+    pub async fn ask(&mut self, nick: &str, msg: &str) -> String {
+        self.conn.say(nick, "#test", msg).await;
+        self.conn.expect_reply("#test").await
+    }
+
+    /// Stops the bot gracefully and returns the QUIT message it sent
+    pub async fn shutdown(mut self) -> String {
+        let _ = self.shutdown.take().unwrap().send(());
+        let quit = self.conn.expect_line("QUIT").await;
+        // the server closes the connection after QUIT, which ends the message loop
+        let _ = self.conn.write.shutdown().await;
+        let task = self.task.take().unwrap();
+        timeout(TIMEOUT, task)
+            .await
+            .expect("the bot didn't shut down")
+            .unwrap()
+            .unwrap();
+        quit
+    }
+}
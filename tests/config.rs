@@ -0,0 +1,77 @@
+mod common;
+
+use common::TempDb;
+use std::{env, fs};
+use uberbot::{config::Secret, UberConfig};
+
+const SECRETS_CONFIG: &str = r##"
+[networks.secret]
+host = "127.0.0.1"
+port = 6667
+tls = false
+username = "uberbot"
+channels = ["#test"]
+sasl_pass_file = "PASS_FILE"
+
+[spotify]
+client_id = "id"
+client_secret = "inline"
+
+[bot]
+prefixes = ["u!"]
+history_depth = 5
+"##;
+
+/// Writes `config` to a temporary file and loads it
+fn load(config: &str, pass_file: &TempDb) -> anyhow::Result<UberConfig> {
+    let file = TempDb::new();
+    fs::write(
+        &file.path,
+        config.replace("PASS_FILE", pass_file.path_str()),
+    )
+    .unwrap();
+    UberConfig::load(&file.path)
+}
+
+#[test]
+fn secrets_are_read_from_files_and_environment() {
+    let pass_file = TempDb::new();
+    fs::write(&pass_file.path, "hunter2\n").unwrap();
+    let cfg = load(SECRETS_CONFIG, &pass_file).unwrap();
+    let network = &cfg.networks["secret"];
+    assert_eq!(network.sasl_pass, Some(Secret::new("hunter2")));
+    assert_eq!(
+        cfg.spotify.unwrap().client_secret,
+        Some(Secret::new("inline"))
+    );
+
+    env::set_var("UBERBOT_NETWORKS_SECRET_SASL_PASS", "from env");
+    let cfg = load(SECRETS_CONFIG, &pass_file);
+    env::remove_var("UBERBOT_NETWORKS_SECRET_SASL_PASS");
+    let network = &cfg.unwrap().networks["secret"];
+    assert_eq!(network.sasl_pass, Some(Secret::new("from env")));
+}
+
+#[test]
+fn inline_secret_conflicts_with_file() {
+    let pass_file = TempDb::new();
+    fs::write(&pass_file.path, "hunter2").unwrap();
+    let config = SECRETS_CONFIG.replace("sasl_pass_file", "sasl_pass = \"x\"\nsasl_pass_file");
+    let err = load(&config, &pass_file).err().unwrap();
+    assert!(err.to_string().contains("can't be used together"), "{err}");
+}
+
+#[test]
+fn missing_secret_file_is_an_error() {
+    assert!(load(SECRETS_CONFIG, &TempDb::new()).is_err());
+}
+
+#[test]
+fn secrets_are_redacted_from_debug_output() {
+    let pass_file = TempDb::new();
+    fs::write(&pass_file.path, "hunter2").unwrap();
+    let debug = format!("{:?}", load(SECRETS_CONFIG, &pass_file).unwrap());
+    assert!(!debug.contains("hunter2"));
+    assert!(!debug.contains("inline"));
+    assert!(debug.contains("[redacted]"));
+}
@@ -0,0 +1,59 @@
+mod common;
+
+use common::{MockServer, TempDb, TestBot, BASE_CONFIG};
+use uberbot::{config::Secret, BotBuilder, UberConfig};
+
+#[tokio::test]
+async fn registers_and_joins_channels() {
+    let bot = TestBot::start().await;
+    let registration = &bot.conn.registration;
+    assert!(registration.iter().any(|l| l == "NICK uberbot"));
+    assert!(registration.iter().any(|l| l.starts_with("USER uberbot ")));
+    assert!(!registration.iter().any(|l| l.starts_with("AUTHENTICATE")));
+}
+
+#[tokio::test]
+async fn authenticates_with_sasl() {
+    let server = MockServer::bind().await;
+    let db = TempDb::new();
+    let mut cfg: UberConfig = toml::from_str(BASE_CONFIG).unwrap();
+    let irc = cfg.irc.as_mut().unwrap();
+    irc.port = server.port();
+    irc.sasl_pass = Some(Secret::new("hunter2"));
+    cfg.bot.db_path = Some(db.path_str().into());
+    let task = tokio::spawn(BotBuilder::new(cfg).run_until(std::future::pending()));
+
+    let conn = server.accept().await;
+    let registration = &conn.registration;
+    let position = |prefix: &str| {
+        registration
+            .iter()
+            .position(|l| l.starts_with(prefix))
+            .unwrap_or_else(|| panic!("{prefix} wasn't sent"))
+    };
+    assert!(position("CAP REQ") < position("AUTHENTICATE PLAIN"));
+    assert!(position("AUTHENTICATE PLAIN") < position("CAP END"));
+    // "uberbot\0uberbot\0hunter2"
+    assert!(registration
+        .iter()
+        .any(|l| l == "AUTHENTICATE dWJlcmJvdAB1YmVyYm90AGh1bnRlcjI="));
+    task.abort();
+}
+
+#[tokio::test]
+async fn quits_on_shutdown() {
+    let bot = TestBot::start().await;
+    let quit = bot.shutdown().await;
+    assert_eq!(quit, "QUIT :überbot shutting down");
+}
+
+#[tokio::test]
+async fn ignores_private_messages() {
+    let mut bot = TestBot::start().await;
+    bot.conn.say("alice", "uberbot", "u!help").await;
+    bot.conn.say("alice", "#test", "u!nope").await;
+    assert_eq!(
+        bot.conn.expect_privmsg().await,
+        ("#test".into(), "Unknown command.".into())
+    );
+}
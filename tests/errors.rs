@@ -0,0 +1,84 @@
+mod common;
+
+use common::{TempDb, TestBot, BASE_CONFIG};
+use uberbot::{async_trait, Command, CommandContext, UserError};
+
+struct Panic;
+
+#[async_trait]
+impl Command for Panic {
+    async fn execute(&self, _ctx: CommandContext) -> anyhow::Result<String> {
+        panic!("oh no")
+    }
+}
+
+struct Fail;
+
+#[async_trait]
+impl Command for Fail {
+    async fn execute(&self, ctx: CommandContext) -> anyhow::Result<String> {
+        if ctx.content.is_some() {
+            Err(UserError::new("You did something wrong.").into())
+        } else {
+            Err(anyhow::anyhow!("secret internal details"))
+        }
+    }
+}
+
+fn with_admin_channel() -> String {
+    BASE_CONFIG.replace("[bot]", "[bot]\nadmin_channel = \"#admin\"")
+}
+
+async fn start(config: &str) -> TestBot {
+    TestBot::start_with(config, TempDb::new(), |b| {
+        b.add_command("panic", Panic).add_command("fail", Fail);
+    })
+    .await
+}
+
+#[tokio::test]
+async fn panics_are_caught() {
+    let mut bot = start(BASE_CONFIG).await;
+    assert_eq!(
+        bot.ask("alice", "u!panic").await,
+        "An internal error has occurred."
+    );
+    // the bot keeps working
+    assert_eq!(bot.ask("alice", "u!nope").await, "Unknown command.");
+}
+
+#[tokio::test]
+async fn panics_are_reported() {
+    let mut bot = start(&with_admin_channel()).await;
+    bot.conn.say("alice", "#test", "u!panic now").await;
+    let report = bot.conn.expect_reply("#admin").await;
+    assert!(report.contains("'panic'"));
+    assert!(report.contains("alice"));
+    assert!(report.contains("\"now\""));
+    assert!(report.contains("oh no"));
+}
+
+#[tokio::test]
+async fn user_errors_are_shown() {
+    let mut bot = start(&with_admin_channel()).await;
+    assert_eq!(
+        bot.ask("alice", "u!fail please").await,
+        "You did something wrong."
+    );
+}
+
+#[tokio::test]
+async fn internal_errors_are_hidden_and_deduplicated() {
+    let mut bot = start(&with_admin_channel()).await;
+    bot.conn.say("alice", "#test", "u!fail").await;
+    let (target, report) = bot.conn.expect_privmsg().await;
+    assert_eq!(target, "#admin");
+    assert!(report.contains("secret internal details"));
+    assert_eq!(
+        bot.conn.expect_reply("#test").await,
+        "An internal error has occurred."
+    );
+    // the same error isn't reported again
+    bot.conn.say("alice", "#test", "u!fail").await;
+    assert_eq!(bot.conn.expect_privmsg().await.0, "#test");
+}
@@ -0,0 +1,197 @@
+mod common;
+
+use common::{TempDb, BASE_CONFIG};
+use std::path::Path;
+use uberbot::{
+    database::{
+        logs::{date_from_path, read_log_quotes, LogMessage, LogParser},
+        transfer::{read_quotes, write_quotes, Format},
+        DbMaintenance, Quote, QuoteLine,
+    },
+    BotBuilder, UberConfig,
+};
+
+#[tokio::test]
+async fn stats_count_quotes_and_authors() {
+    let db = TempDb::new();
+    db.seed_quotes(&[("alice", "hello"), ("bob", "hi"), ("Alice", "bye")])
+        .await;
+    let stats = DbMaintenance::open(&db.path).unwrap().stats().unwrap();
+    assert_eq!(stats.quotes, 3);
+    assert_eq!(stats.authors, 2);
+    assert!(stats.size > 0);
+}
+
+#[test]
+fn open_fails_for_missing_database() {
+    let db = TempDb::new();
+    assert!(DbMaintenance::open(&db.path).is_err());
+    assert!(!db.path.exists());
+}
+
+#[tokio::test]
+async fn backup_copies_quotes() {
+    let (db, backup) = (TempDb::new(), TempDb::new());
+    db.seed_quotes(&[("alice", "hello")]).await;
+    let maintenance = DbMaintenance::open(&db.path).unwrap();
+    maintenance.vacuum().unwrap();
+    maintenance.backup(&backup.path).unwrap();
+    // existing files are never overwritten
+    assert!(maintenance.backup(&backup.path).is_err());
+    let stats = DbMaintenance::open(&backup.path).unwrap().stats().unwrap();
+    assert_eq!(stats.quotes, 1);
+}
+
+#[tokio::test]
+async fn check_reports_problems() {
+    let db = TempDb::new();
+    let mut cfg: UberConfig = toml::from_str(BASE_CONFIG).unwrap();
+    cfg.bot.db_path = Some(db.path_str().into());
+    cfg.bot.ignored_triggers = Some([("nope".to_string(), vec!["*".to_string()])].into());
+    let warnings = BotBuilder::new(cfg.clone()).check().unwrap();
+    assert_eq!(warnings.len(), 2, "{warnings:?}");
+    assert!(warnings[0].contains("unknown trigger 'nope'"));
+    assert!(warnings[1].contains("will be created"));
+
+    db.seed_quotes(&[("alice", "hello")]).await;
+    cfg.bot.ignored_triggers = None;
+    assert!(BotBuilder::new(cfg.clone()).check().unwrap().is_empty());
+
+    cfg.bot.prefixes.clear();
+    assert!(BotBuilder::new(cfg).check().is_err());
+}
+
+#[tokio::test]
+async fn export_and_import_keep_ids_and_skip_duplicates() {
+    let (db, copy) = (TempDb::new(), TempDb::new());
+    db.seed_quotes(&[("alice", "hello"), ("bob", "hi")]).await;
+    copy.seed_quotes(&[]).await;
+    let mut conversation = Quote::conversation(vec![
+        QuoteLine {
+            speaker: "alice".into(),
+            message: "knock knock".into(),
+        },
+        QuoteLine {
+            speaker: "bob".into(),
+            message: "who's there?".into(),
+        },
+    ]);
+    conversation.id = Some(10);
+    let mut source = DbMaintenance::open(&db.path).unwrap();
+    assert_eq!(source.import(vec![conversation]).unwrap(), (1, 0));
+    let quotes = source.export().unwrap();
+
+    let mut json = Vec::new();
+    write_quotes(Format::Json, &quotes, &mut json).unwrap();
+    let mut target = DbMaintenance::open(&copy.path).unwrap();
+    let imported = read_quotes(Format::Json, json.as_slice()).unwrap();
+    assert_eq!(target.import(imported).unwrap(), (3, 0));
+    let exported = target.export().unwrap();
+    let ids: Vec<_> = exported.iter().map(|q| q.id).collect();
+    assert_eq!(ids, [Some(1), Some(2), Some(10)]);
+    assert_eq!(exported[2].lines, quotes[2].lines);
+    assert_eq!(exported[2].author, "alice, bob");
+
+    let mut csv = Vec::new();
+    write_quotes(Format::Csv, &quotes, &mut csv).unwrap();
+    let imported = read_quotes(Format::Csv, csv.as_slice()).unwrap();
+    assert_eq!(target.import(imported).unwrap(), (0, 3));
+}
+
+#[test]
+fn import_minimal_csv() {
+    let csv = "author,quote\nalice,\"hello, world\"\n";
+    let quotes = read_quotes(Format::Csv, csv.as_bytes()).unwrap();
+    assert_eq!(quotes.len(), 1);
+    assert_eq!(quotes[0].quote, "hello, world");
+    assert_eq!(quotes[0].id, None);
+    let err = read_quotes(Format::Csv, format!("{csv}carol,\n").as_bytes()).unwrap_err();
+    assert_eq!(err.to_string(), "Quote 2 has no author or text");
+    assert!(Format::from_path(Path::new("quotes.txt")).is_err());
+}
+
+#[test]
+fn parse_log_formats() {
+    // 2023-01-02 00:00:00 UTC
+    const DAY: i64 = 1_672_617_600;
+    let message = |timestamp, nick: &str, text: &str| LogMessage {
+        timestamp,
+        nick: nick.into(),
+        text: text.into(),
+    };
+    let mut irssi = LogParser::default();
+    assert_eq!(
+        irssi.parse_line("12:34 <@alice> no date yet"),
+        Some(message(None, "alice", "no date yet"))
+    );
+    assert_eq!(irssi.parse_line("--- Day changed Mon Jan 02 2023"), None);
+    assert_eq!(
+        irssi.parse_line("12:34 < bob> hello <3"),
+        Some(message(Some(DAY + 45240), "bob", "hello <3"))
+    );
+    assert_eq!(
+        irssi.parse_line("12:35 -!- carol [~c@host] has joined #test"),
+        None
+    );
+    assert_eq!(irssi.parse_line("12:35  * bob waves"), None);
+
+    let mut znc = LogParser::new(date_from_path(Path::new("logs/#test_20230102.log")));
+    assert_eq!(
+        znc.parse_line("[00:00:05] <+carol> hi"),
+        Some(message(Some(DAY + 5), "carol", "hi"))
+    );
+    assert_eq!(
+        znc.parse_line("[00:00:06] *** Joins: dave (dave@host)"),
+        None
+    );
+
+    let mut weechat = LogParser::default();
+    assert_eq!(
+        weechat.parse_line("2023-01-02 00:01:00\t@dave\tsee\tthis"),
+        Some(message(Some(DAY + 60), "dave", "see\tthis"))
+    );
+    assert_eq!(
+        weechat.parse_line("2023-01-02 00:01:00\t-->\tdave has joined"),
+        None
+    );
+    assert_eq!(
+        weechat.parse_line("2023-01-02 00:01:00\t *\tdave waves"),
+        None
+    );
+
+    assert_eq!(date_from_path(Path::new("2023-01-02.log")), Some(DAY));
+    assert_eq!(date_from_path(Path::new("#test.log")), None);
+}
+
+#[test]
+fn import_log_excerpts() {
+    let db = TempDb::new();
+    let log = "\
+--- Log opened Mon Jan 02 00:00:00 2023
+00:00 <alice> first
+00:01 <alice> second
+
+00:02 -!- bob [~b@host] has joined #Test
+00:03 <bob> knock knock
+00:04 <@Alice> who's there?
+
+";
+    let logs = read_log_quotes(log.as_bytes(), None, Some("#Test")).unwrap();
+    assert_eq!(logs.skipped, 1);
+    assert_eq!(logs.quotes.len(), 2);
+    assert_eq!(logs.quotes[0].quote, "first | second");
+    assert_eq!(logs.quotes[0].timestamp, Some(1_672_617_600));
+    assert_eq!(logs.quotes[0].channel.as_deref(), Some("#test"));
+    assert_eq!(
+        logs.quotes[1].quote,
+        "<bob> knock knock <Alice> who's there?"
+    );
+
+    let mut maintenance = DbMaintenance::create(&db.path).unwrap();
+    maintenance.migrate().unwrap();
+    assert_eq!(maintenance.import(logs.quotes).unwrap(), (2, 0));
+    let exported = maintenance.export().unwrap();
+    assert_eq!(exported[1].author, "bob, alice");
+    assert_eq!(exported[1].lines.len(), 2);
+    assert!(maintenance.opted_out().unwrap().is_empty());
+}
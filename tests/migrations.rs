@@ -0,0 +1,91 @@
+mod common;
+
+use common::{TempDb, TestBot, BASE_CONFIG};
+use rusqlite::Connection;
+use uberbot::{database::DbMaintenance, DbExecutor};
+
+/// Creates a database with the schema used before migrations were introduced
+fn legacy_db(quotes: &[(&str, &str)]) -> TempDb {
+    let db = TempDb::new();
+    let conn = Connection::open(&db.path).unwrap();
+    conn.execute(
+        "create virtual table quotes using fts5(username, quote)",
+        [],
+    )
+    .unwrap();
+    for (author, quote) in quotes {
+        conn.execute(
+            "insert into quotes(username, quote) values(?, ?)",
+            [author, quote],
+        )
+        .unwrap();
+    }
+    db
+}
+
+#[tokio::test]
+async fn legacy_quotes_are_preserved() {
+    let db = legacy_db(&[("alice", "I like cats"), ("bob", "dogs are better")]);
+    let mut bot = TestBot::start_with(BASE_CONFIG, db, |_| {}).await;
+    assert_eq!(
+        bot.ask("carol", "u!quot alice").await,
+        "\"I like cats\" ~alice (#1)"
+    );
+    assert_eq!(
+        bot.ask("carol", "u!qsearch better").await,
+        "\"dogs are \x02better\x02\" ~bob (#2)"
+    );
+    let db = DbMaintenance::open(&bot.db.path).unwrap();
+    assert_eq!(db.pending_migrations().unwrap(), 0);
+}
+
+#[test]
+fn stats_work_before_migrating() {
+    let db = legacy_db(&[("alice", "I like cats"), ("bob", "dogs are better")]);
+    let maintenance = DbMaintenance::open(&db.path).unwrap();
+    let stats = maintenance.stats().unwrap();
+    assert_eq!((stats.quotes, stats.authors), (2, 2));
+    maintenance.vacuum().unwrap();
+    assert!(maintenance.pending_migrations().unwrap() > 0);
+}
+
+#[tokio::test]
+async fn grab_stores_metadata() {
+    let mut bot = TestBot::start().await;
+    bot.conn.say("alice", "#test", "hello").await;
+    bot.ask("bob", "u!grab alice").await;
+    let conn = Connection::open(&bot.db.path).unwrap();
+    let (channel, grabbed_by, timestamp): (String, String, i64) = conn
+        .query_row(
+            "select channel, grabbed_by, timestamp from quotes",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+        )
+        .unwrap();
+    assert_eq!((channel.as_str(), grabbed_by.as_str()), ("default/#test", "bob"));
+    assert!(timestamp > 1_600_000_000);
+}
+
+#[test]
+fn newer_schema_is_rejected() {
+    let db = TempDb::new();
+    let conn = Connection::open(&db.path).unwrap();
+    conn.pragma_update(None, "user_version", 1000).unwrap();
+    drop(conn);
+    let err = DbExecutor::create(db.path_str()).err().unwrap();
+    assert!(err.to_string().contains("newer"), "{err}");
+}
+
+#[tokio::test]
+async fn conversation_lines_are_removed_with_their_quote() {
+    let mut bot = TestBot::start().await;
+    bot.conn.say("alice", "#test", "hi").await;
+    bot.conn.say("bob", "#test", "hello").await;
+    bot.ask("carol", "u!convo 2").await;
+    assert_eq!(bot.ask("carol", "u!unquote 1").await, "Quote #1 removed.");
+    let conn = Connection::open(&bot.db.path).unwrap();
+    let lines: i64 = conn
+        .query_row("select count(*) from quote_lines", [], |row| row.get(0))
+        .unwrap();
+    assert_eq!(lines, 0);
+}
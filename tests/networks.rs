@@ -0,0 +1,183 @@
+mod common;
+
+use common::{MockConnection, MockServer, TempDb};
+use tokio::sync::oneshot;
+use uberbot::{async_trait, BotBuilder, Command, CommandContext, UberConfig};
+
+struct Panic;
+
+#[async_trait]
+impl Command for Panic {
+    async fn execute(&self, _ctx: CommandContext) -> anyhow::Result<String> {
+        panic!("oh no")
+    }
+}
+
+fn config(port_a: u16, port_b: u16, extra: &str) -> String {
+    format!(
+        r##"
+[networks.a]
+host = "127.0.0.1"
+port = {port_a}
+tls = false
+username = "uberbot"
+channels = ["#test"]
+
+[networks.b]
+host = "127.0.0.1"
+port = {port_b}
+tls = false
+username = "uberbot"
+channels = ["#test"]
+prefixes = ["b!"]
+
+[bot]
+prefixes = ["a!"]
+history_depth = 5
+{extra}
+"##
+    )
+}
+
+struct TwoNetworks {
+    a: MockConnection,
+    b: MockConnection,
+    db: TempDb,
+    _shutdown: oneshot::Sender<()>,
+}
+
+async fn start(extra: &str) -> TwoNetworks {
+    let (server_a, server_b) = (MockServer::bind().await, MockServer::bind().await);
+    let db = TempDb::new();
+    let extra = format!("db_path = {:?}\n{extra}", db.path_str());
+    let cfg: UberConfig =
+        toml::from_str(&config(server_a.port(), server_b.port(), &extra)).unwrap();
+    let mut builder = BotBuilder::new(cfg);
+    builder.add_builtin_commands().await.unwrap();
+    builder.add_command("panic", Panic);
+    let (shutdown, shutdown_rx) = oneshot::channel::<()>();
+    tokio::spawn(builder.run_until(async {
+        let _ = shutdown_rx.await;
+    }));
+    let mut a = server_a.accept().await;
+    let mut b = server_b.accept().await;
+    a.expect_line("JOIN ").await;
+    b.expect_line("JOIN ").await;
+    TwoNetworks {
+        a,
+        b,
+        db,
+        _shutdown: shutdown,
+    }
+}
+
+#[tokio::test]
+async fn networks_have_their_own_prefixes() {
+    let mut bot = start("").await;
+    bot.a.say("alice", "#test", "b!nope").await;
+    bot.a.say("alice", "#test", "a!nope").await;
+    assert_eq!(bot.a.expect_reply("#test").await, "Unknown command.");
+    bot.b.say("alice", "#test", "b!nope").await;
+    assert_eq!(bot.b.expect_reply("#test").await, "Unknown command.");
+}
+
+#[tokio::test]
+async fn history_is_per_network() {
+    let mut bot = start("").await;
+    bot.a.say("alice", "#test", "hello from a").await;
+    bot.b.say("bob", "#test", "b!grab alice").await;
+    assert_eq!(
+        bot.b.expect_reply("#test").await,
+        "No previous messages to grab."
+    );
+    bot.a.say("bob", "#test", "a!grab alice").await;
+    assert_eq!(
+        bot.a.expect_reply("#test").await,
+        "Quote #1 added (1 messages)."
+    );
+    // channels with the same name on other networks have their own quotes
+    bot.b.say("bob", "#test", "b!quot alice").await;
+    assert_eq!(
+        bot.b.expect_reply("#test").await,
+        "No quotes found from this user."
+    );
+}
+
+#[tokio::test]
+async fn quote_namespaces_span_networks() {
+    let mut bot = start(
+        r##"
+[channels."a/#test"]
+quote_namespace = "shared"
+[channels."b/#other"]
+quote_namespace = "shared"
+"##,
+    )
+    .await;
+    bot.a.say("alice", "#test", "hello from a").await;
+    bot.a.say("bob", "#test", "a!grab alice").await;
+    bot.a.expect_reply("#test").await;
+    bot.b.say("bob", "#other", "b!quot alice").await;
+    assert_eq!(
+        bot.b.expect_reply("#other").await,
+        "\"hello from a\" ~alice (#1)"
+    );
+    bot.b.say("bob", "#test", "b!quot alice").await;
+    assert_eq!(
+        bot.b.expect_reply("#test").await,
+        "No quotes found from this user."
+    );
+    // quotes grabbed before channels were qualified are shown on every network
+    let conn = rusqlite::Connection::open(&bot.db.path).unwrap();
+    conn.execute("update quotes set channel = '#test'", [])
+        .unwrap();
+    bot.b.say("bob", "#test", "b!quot alice").await;
+    assert_eq!(
+        bot.b.expect_reply("#test").await,
+        "\"hello from a\" ~alice (#1)"
+    );
+}
+
+#[tokio::test]
+async fn opting_out_forgets_history_of_all_networks() {
+    let mut bot = start("").await;
+    bot.a.say("alice", "#test", "hello from a").await;
+    bot.b.say("alice", "#test", "b!optout").await;
+    bot.b.expect_reply("#test").await;
+    bot.a.say("bob", "#test", "a!convo 1").await;
+    assert_eq!(
+        bot.a.expect_reply("#test").await,
+        "No previous messages to grab."
+    );
+}
+
+#[tokio::test]
+async fn reports_go_to_qualified_admin_channel() {
+    let mut bot = start("admin_channel = \"b/#admin\"").await;
+    bot.a.say("alice", "#test", "a!panic").await;
+    assert_eq!(
+        bot.a.expect_reply("#test").await,
+        "An internal error has occurred."
+    );
+    assert!(bot.b.expect_reply("#admin").await.contains("oh no"));
+}
+
+#[test]
+fn targets_have_to_be_qualified() {
+    let web = "[web]\nlisten = \"127.0.0.1:8080\"\n[web.webhooks]\n";
+    let parse = |extra: &str| toml::from_str::<UberConfig>(&config(1, 2, extra)).unwrap();
+    if cfg!(feature = "web") {
+        assert!(parse(&format!("{web}dev = \"#dev\"")).validate().is_err());
+        assert!(parse(&format!("{web}dev = \"c/#dev\"")).validate().is_err());
+        assert!(parse(&format!("{web}dev = \"b/#dev\"")).validate().is_ok());
+    }
+    assert!(parse("admin_channel = \"c/#admin\"").validate().is_err());
+    assert!(parse("admin_channel = \"#admin\"").validate().is_ok());
+    let qotd = "[qotd]\ntime = \"12:00\"\n";
+    assert!(parse(&format!("{qotd}channels = [\"#test\"]"))
+        .validate()
+        .is_err());
+    assert!(parse(&format!("{qotd}channels = [\"b/#test\"]"))
+        .validate()
+        .is_ok());
+}
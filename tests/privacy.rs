@@ -0,0 +1,107 @@
+mod common;
+
+use common::{TempDb, TestBot, BASE_CONFIG};
+
+#[tokio::test]
+async fn opted_out_users_are_not_stored() {
+    let mut bot = TestBot::start().await;
+    bot.conn.say("alice", "#test", "hello").await;
+    assert_eq!(
+        bot.ask("alice", "u!optout").await,
+        "Your messages won't be stored or quoted anymore. Use 'optin' to undo this."
+    );
+    assert_eq!(
+        bot.ask("alice", "u!optout").await,
+        "You've already opted out."
+    );
+    bot.conn.say("alice", "#test", "secret").await;
+    assert_eq!(
+        bot.ask("bob", "u!grab alice").await,
+        "alice doesn't want to be quoted."
+    );
+    assert_eq!(
+        bot.ask("alice", "u!optin").await,
+        "Your messages can be quoted again."
+    );
+    // neither the old messages nor the ones sent while opted out were kept
+    assert_eq!(
+        bot.ask("bob", "u!grab alice").await,
+        "No previous messages to grab."
+    );
+    assert_eq!(bot.ask("alice", "u!optin").await, "You haven't opted out.");
+}
+
+#[tokio::test]
+async fn forgetme_requires_confirmation() {
+    let db = TempDb::new();
+    db.seed_quotes(&[("alice", "first"), ("bob", "second")])
+        .await;
+    let mut bot = TestBot::start_with(BASE_CONFIG, db, |_| {}).await;
+    bot.conn.say("bob", "#test", "hello").await;
+    bot.ask("alice", "u!grab bob").await;
+    bot.ask("alice", "u!+1 2").await;
+    assert_eq!(
+        bot.ask("alice", "u!forgetme").await,
+        "This will delete your 1 quotes and your message history, and I won't store your messages anymore. Use 'forgetme' again within 60 seconds to confirm."
+    );
+    assert_eq!(
+        bot.ask("alice", "u!forgetme").await,
+        "Forgot alice, 1 quotes were deleted."
+    );
+    assert_eq!(
+        bot.ask("bob", "u!quot alice").await,
+        "No quotes found from this user."
+    );
+    // votes and the grabber's name are removed as well
+    assert_eq!(bot.ask("bob", "u!qtop").await, "\"second\" ~bob (#2) +0");
+    bot.conn.expect_reply("#test").await;
+    assert_eq!(
+        bot.ask("bob", "u!qstats").await,
+        "2 quotes. Most quoted: bob (2)."
+    );
+    bot.conn.say("alice", "#test", "still here").await;
+    assert_eq!(
+        bot.ask("bob", "u!grab alice").await,
+        "alice doesn't want to be quoted."
+    );
+}
+
+#[tokio::test]
+async fn admins_can_forget_others() {
+    let config = format!("{BASE_CONFIG}admins = [\"root\"]\n");
+    let db = TempDb::new();
+    db.seed_quotes(&[("alice", "first")]).await;
+    let mut bot = TestBot::start_with(&config, db, |_| {}).await;
+    assert_eq!(
+        bot.ask("bob", "u!forgetme alice").await,
+        "Only admins can make me forget other users."
+    );
+    assert_eq!(
+        bot.ask("root", "u!forgetme alice").await,
+        "Forgot alice, 1 quotes were deleted."
+    );
+}
+
+#[tokio::test]
+async fn opt_outs_are_loaded_at_startup() {
+    let db = TempDb::new();
+    db.seed_quotes(&[]).await;
+    let conn = rusqlite::Connection::open(&db.path).unwrap();
+    conn.execute("insert into opt_outs(nick) values('Alice')", [])
+        .unwrap();
+    let mut bot = TestBot::start_with(BASE_CONFIG, db, |_| {}).await;
+    bot.conn.say("alice", "#test", "secret").await;
+    assert_eq!(
+        bot.ask("bob", "u!grab alice").await,
+        "alice doesn't want to be quoted."
+    );
+    assert_eq!(
+        bot.ask("alice", "u!optin").await,
+        "Your messages can be quoted again."
+    );
+    bot.conn.say("alice", "#test", "public").await;
+    assert_eq!(
+        bot.ask("bob", "u!grab alice").await,
+        "Quote #1 added (1 messages)."
+    );
+}
@@ -0,0 +1,54 @@
+mod common;
+
+use common::{TempDb, TestBot, BASE_CONFIG};
+use std::thread;
+use uberbot::{database::DbExecutor, UberConfig};
+
+#[tokio::test]
+async fn qotd_stays_the_same_for_a_day() {
+    let db = TempDb::new();
+    db.seed_quotes(&[("alice", "hello"), ("bob", "hi"), ("carol", "hey")])
+        .await;
+    let mut bot = TestBot::start_with(BASE_CONFIG, db, |_| {}).await;
+    let first = bot.ask("alice", "u!qotd").await;
+    assert!(first.starts_with("Quote of the day: \""), "{first}");
+    for _ in 0..3 {
+        assert_eq!(bot.ask("bob", "u!qotd").await, first);
+    }
+}
+
+#[tokio::test]
+async fn qotd_is_not_repeated_within_the_window() {
+    let db = TempDb::new();
+    db.seed_quotes(&[("alice", "hello"), ("bob", "hi")]).await;
+    let (exec, conn) = DbExecutor::create(db.path_str()).unwrap();
+    let exec_thread = thread::spawn(move || exec.run());
+    let channels = vec!["#test".to_string()];
+    // a start of the day in the future makes every call pick a new quote
+    let pick = |window| conn.quote_of_the_day("#test".into(), channels.clone(), i64::MAX, window);
+    let first = pick(3600).await.unwrap().unwrap();
+    let second = pick(3600).await.unwrap().unwrap();
+    assert_ne!(first.id, second.id);
+    assert!(pick(3600).await.unwrap().is_none());
+    // other channels have their own quote of the day
+    let other = conn
+        .quote_of_the_day("#other".into(), vec!["#other".into()], 0, 3600)
+        .await
+        .unwrap();
+    assert!(other.is_some());
+    // once the window has passed, quotes can be picked again
+    assert!(pick(0).await.unwrap().is_some());
+    drop(conn);
+    exec_thread.join().unwrap();
+}
+
+#[test]
+fn qotd_time_is_validated() {
+    let parse = |time: &str| {
+        let config = format!("{BASE_CONFIG}\n[qotd]\nchannels = [\"#test\"]\ntime = \"{time}\"\n");
+        toml::from_str::<UberConfig>(&config).unwrap().validate()
+    };
+    assert!(parse("08:30").is_ok());
+    assert!(parse("24:00").is_err());
+    assert!(parse("noon").is_err());
+}
@@ -0,0 +1,517 @@
+mod common;
+
+use common::{TempDb, TestBot, BASE_CONFIG};
+use rusqlite::Connection;
+
+#[tokio::test]
+async fn grab_and_quot() {
+    let mut bot = TestBot::start().await;
+    bot.conn.say("alice", "#test", "first").await;
+    bot.conn.say("alice", "#test", "second").await;
+    assert_eq!(
+        bot.ask("bob", "u!grab 2 alice").await,
+        "Quote #1 added (2 messages)."
+    );
+    assert_eq!(
+        bot.ask("bob", "u!quot alice").await,
+        "\"first | second\" ~alice (#1)"
+    );
+}
+
+#[tokio::test]
+async fn grab_errors() {
+    let mut bot = TestBot::start().await;
+    assert_eq!(bot.ask("bob", "u!grab").await, "Invalid usage.");
+    assert_eq!(
+        bot.ask("bob", "u!grab alice").await,
+        "No previous messages to grab."
+    );
+    assert_eq!(
+        bot.ask("bob", "u!grab bob").await,
+        "You can't grab yourself."
+    );
+    assert_eq!(
+        bot.ask("bob", "u!grab 0 alice").await,
+        "So are you going to grab anything?"
+    );
+    assert_eq!(
+        bot.ask("bob", "u!grab x alice").await,
+        "Invalid message count."
+    );
+}
+
+#[tokio::test]
+async fn quot_without_quotes() {
+    let mut bot = TestBot::start().await;
+    assert_eq!(
+        bot.ask("bob", "u!quot alice").await,
+        "No quotes found from this user."
+    );
+}
+
+#[tokio::test]
+async fn search_pages() {
+    let db = TempDb::new();
+    db.seed_quotes(&[
+        ("alice", "cats are great"),
+        ("bob", "I have two cats"),
+        ("carol", "dogs are better"),
+        ("dave", "cats cats cats"),
+        ("erin", "no more cats"),
+    ])
+    .await;
+    let mut bot = TestBot::start_with(BASE_CONFIG, db, |_| {}).await;
+    assert_eq!(
+        bot.ask("alice", "u!qnext").await,
+        "You need to initiate a search first using 'qsearch'."
+    );
+
+    // the best matches come first, with the matching words in bold
+    let mut page = vec![bot.ask("alice", "u!qsearch cats").await];
+    for _ in 0..3 {
+        page.push(bot.conn.expect_reply("#test").await);
+    }
+    assert_eq!(
+        page,
+        [
+            "\"\x02cats\x02 \x02cats\x02 \x02cats\x02\" ~dave (#4)",
+            "\"\x02cats\x02 are great\" ~alice (#1)",
+            "\"no more \x02cats\x02\" ~erin (#5)",
+            "Page 1, use 'qnext' for more results."
+        ]
+    );
+    let mut page = vec![bot.ask("alice", "u!qnext").await];
+    page.push(bot.conn.expect_reply("#test").await);
+    assert_eq!(page, ["\"I have two \x02cats\x02\" ~bob (#2)", "Page 2."]);
+    assert_eq!(bot.ask("alice", "u!qnext").await, "No results.");
+    // the page didn't change, so the previous one is the first page
+    assert_eq!(
+        bot.ask("alice", "u!qprev").await,
+        "\"\x02cats\x02 \x02cats\x02 \x02cats\x02\" ~dave (#4)"
+    );
+    bot.conn.expect_reply("#test").await;
+    bot.conn.expect_reply("#test").await;
+    bot.conn.expect_reply("#test").await;
+    assert_eq!(
+        bot.ask("alice", "u!qpage 2").await,
+        "\"I have two \x02cats\x02\" ~bob (#2)"
+    );
+    bot.conn.expect_reply("#test").await;
+    assert_eq!(bot.ask("alice", "u!qpage 0").await, "Invalid page number.");
+}
+
+#[tokio::test]
+async fn search_filters() {
+    let mut bot = TestBot::start().await;
+    bot.conn.say("alice", "#test", "cats are great").await;
+    bot.ask("carol", "u!grab alice").await;
+    bot.conn.say("bob", "#test", "cats are evil").await;
+    bot.ask("carol", "u!grab bob").await;
+    bot.conn.say("bob", "#test", "dogs are great").await;
+    bot.ask("carol", "u!grab bob").await;
+    let conn = Connection::open(&bot.db.path).unwrap();
+    // 2023-11-14, 2023-11-15 and 2023-11-16
+    conn.execute(
+        "update quotes set timestamp = 1699920000 + (id - 1) * 86400",
+        [],
+    )
+    .unwrap();
+    assert_eq!(
+        bot.ask("carol", "u!qsearch author:bob cats").await,
+        "\"\x02cats\x02 are evil\" ~bob (#2)"
+    );
+    // without search terms, the newest quotes come first
+    assert_eq!(
+        bot.ask("carol", "u!qsearch author:bob").await,
+        "\"dogs are great\" ~bob (#3)"
+    );
+    bot.conn.expect_reply("#test").await;
+    assert_eq!(
+        bot.ask("carol", "u!qsearch after:2023-11-14 before:2023-11-16 are")
+            .await,
+        "\"cats \x02are\x02 evil\" ~bob (#2)"
+    );
+    assert_eq!(
+        bot.ask("carol", "u!qsearch before:2023-11-14 cats").await,
+        "No results."
+    );
+    assert_eq!(
+        bot.ask("carol", "u!qsearch after:yesterday").await,
+        "Invalid date, use YYYY-MM-DD."
+    );
+}
+
+#[tokio::test]
+async fn search_with_punctuation() {
+    let db = TempDb::new();
+    db.seed_quotes(&[
+        ("alice", "what's up with C++?"),
+        ("bob", "hello-world \"demo\""),
+    ])
+    .await;
+    let mut bot = TestBot::start_with(BASE_CONFIG, db, |_| {}).await;
+    assert_eq!(
+        bot.ask("carol", "u!qsearch what's").await,
+        "\"\x02what's\x02 up with C++?\" ~alice (#1)"
+    );
+    for query in ["cats?", "C++", "hello-world", "\"demo", "AND", "*"] {
+        let reply = bot.ask("carol", &format!("u!qsearch {query}")).await;
+        assert!(!reply.contains("error"), "{query}: {reply}");
+    }
+    assert!(bot
+        .ask("carol", "u!qsearch hello-world")
+        .await
+        .ends_with("~bob (#2)"));
+}
+
+#[tokio::test]
+async fn quote_by_id() {
+    let db = TempDb::new();
+    db.seed_quotes(&[("alice", "hello"), ("bob", "hi")]).await;
+    let mut bot = TestBot::start_with(BASE_CONFIG, db, |_| {}).await;
+    assert_eq!(bot.ask("carol", "u!quote 2").await, "\"hi\" ~bob (#2)");
+    assert_eq!(
+        bot.ask("carol", "u!quote #1").await,
+        "\"hello\" ~alice (#1)"
+    );
+    assert_eq!(
+        bot.ask("carol", "u!quote 3").await,
+        "Quote #3 doesn't exist."
+    );
+    assert_eq!(bot.ask("carol", "u!quote x").await, "Invalid quote id.");
+}
+
+#[tokio::test]
+async fn unquote_by_grabber_or_admin() {
+    let config = format!("{BASE_CONFIG}admins = [\"Root\"]\n");
+    let mut bot = TestBot::start_with(&config, TempDb::new(), |_| {}).await;
+    bot.conn.say("alice", "#test", "first").await;
+    bot.ask("bob", "u!grab alice").await;
+    bot.conn.say("alice", "#test", "second").await;
+    bot.ask("bob", "u!grab alice").await;
+    assert_eq!(
+        bot.ask("carol", "u!unquote 1").await,
+        "Only the user who grabbed this quote or an admin can remove it."
+    );
+    assert_eq!(bot.ask("bob", "u!unquote 1").await, "Quote #1 removed.");
+    assert_eq!(bot.ask("root", "u!unquote #2").await, "Quote #2 removed.");
+    assert_eq!(
+        bot.ask("root", "u!unquote 2").await,
+        "Quote #2 doesn't exist."
+    );
+    assert_eq!(
+        bot.ask("carol", "u!quot alice").await,
+        "No quotes found from this user."
+    );
+}
+
+#[tokio::test]
+async fn ungrab_removes_last_grab() {
+    let mut bot = TestBot::start().await;
+    assert_eq!(
+        bot.ask("bob", "u!ungrab").await,
+        "You haven't grabbed anything recently."
+    );
+    bot.conn.say("alice", "#test", "first").await;
+    bot.ask("bob", "u!grab alice").await;
+    bot.conn.say("alice", "#test", "oops").await;
+    bot.ask("bob", "u!grab alice").await;
+    // only the grabber can undo their grab
+    assert_eq!(
+        bot.ask("carol", "u!ungrab").await,
+        "You haven't grabbed anything recently."
+    );
+    assert_eq!(
+        bot.ask("bob", "u!ungrab").await,
+        "Removed \"oops\" ~alice (#2)"
+    );
+    assert_eq!(
+        bot.ask("bob", "u!quot alice").await,
+        "\"first\" ~alice (#1)"
+    );
+}
+
+#[tokio::test]
+async fn ungrab_window() {
+    let config = format!("{BASE_CONFIG}ungrab_window = 0\n");
+    let mut bot = TestBot::start_with(&config, TempDb::new(), |_| {}).await;
+    bot.conn.say("alice", "#test", "first").await;
+    bot.ask("bob", "u!grab alice").await;
+    tokio::time::sleep(std::time::Duration::from_millis(1100)).await;
+    assert_eq!(
+        bot.ask("bob", "u!ungrab").await,
+        "You haven't grabbed anything recently."
+    );
+}
+
+#[tokio::test]
+async fn voting_and_top() {
+    let db = TempDb::new();
+    db.seed_quotes(&[("alice", "hello"), ("bob", "hi"), ("alice", "bye")])
+        .await;
+    let mut bot = TestBot::start_with(BASE_CONFIG, db, |_| {}).await;
+    assert_eq!(
+        bot.ask("carol", "u!+1 2").await,
+        "Quote #2 now has a score of +1."
+    );
+    // only one vote per user, voting again replaces the vote
+    assert_eq!(
+        bot.ask("Carol", "u!+1 #2").await,
+        "Quote #2 now has a score of +1."
+    );
+    assert_eq!(
+        bot.ask("dave", "u!+1 3").await,
+        "Quote #3 now has a score of +1."
+    );
+    assert_eq!(
+        bot.ask("carol", "u!-1 3").await,
+        "Quote #3 now has a score of +0."
+    );
+    assert_eq!(
+        bot.ask("carol", "u!-1 1").await,
+        "Quote #1 now has a score of -1."
+    );
+    assert_eq!(bot.ask("carol", "u!+1 9").await, "Quote #9 doesn't exist.");
+
+    let mut top = vec![bot.ask("carol", "u!qtop").await];
+    for _ in 0..2 {
+        top.push(bot.conn.expect_reply("#test").await);
+    }
+    assert_eq!(
+        top,
+        [
+            "\"hi\" ~bob (#2) +1",
+            "\"bye\" ~alice (#3) +0",
+            "\"hello\" ~alice (#1) -1"
+        ]
+    );
+    assert_eq!(
+        bot.ask("carol", "u!qtop alice").await,
+        "\"bye\" ~alice (#3) +0"
+    );
+    assert_eq!(
+        bot.conn.expect_reply("#test").await,
+        "\"hello\" ~alice (#1) -1"
+    );
+    assert_eq!(bot.ask("carol", "u!qtop nobody").await, "No quotes found.");
+}
+
+#[tokio::test]
+async fn weighted_quot() {
+    let config = format!("{BASE_CONFIG}weighted_quotes = true\n");
+    let db = TempDb::new();
+    db.seed_quotes(&[("alice", "hello"), ("bob", "hi")]).await;
+    let mut bot = TestBot::start_with(&config, db, |_| {}).await;
+    bot.ask("carol", "u!-1 1").await;
+    // downvoted quotes can still be picked
+    assert_eq!(
+        bot.ask("carol", "u!quot alice").await,
+        "\"hello\" ~alice (#1)"
+    );
+    assert_eq!(
+        bot.ask("carol", "u!quot nobody").await,
+        "No quotes found from this user."
+    );
+}
+
+#[tokio::test]
+async fn duplicate_grabs() {
+    let mut bot = TestBot::start().await;
+    bot.conn.say("alice", "#test", "first").await;
+    bot.conn.say("alice", "#test", "second").await;
+    assert_eq!(
+        bot.ask("bob", "u!grab alice").await,
+        "Quote #1 added (1 messages)."
+    );
+    assert_eq!(
+        bot.ask("carol", "u!grab alice").await,
+        "This was already quoted as #1."
+    );
+    // overlapping grabs are duplicates too
+    assert_eq!(
+        bot.ask("carol", "u!grab 2 alice").await,
+        "This was already quoted as #1."
+    );
+    bot.conn.say("dave", "#test", "  SECOND ").await;
+    assert_eq!(
+        bot.ask("bob", "u!grab dave").await,
+        "Quote #2 added (1 messages)."
+    );
+}
+
+#[tokio::test]
+async fn near_duplicates_are_detected() {
+    let db = TempDb::new();
+    db.seed_quotes(&[("alice", "Hello   World")]).await;
+    let mut bot = TestBot::start_with(BASE_CONFIG, db, |_| {}).await;
+    bot.conn.say("alice", "#test", "hello world").await;
+    assert_eq!(
+        bot.ask("bob", "u!grab alice").await,
+        "This was already quoted as #1."
+    );
+}
+
+#[tokio::test]
+async fn conversation_grabs() {
+    let mut bot = TestBot::start().await;
+    bot.conn.say("alice", "#test", "knock knock").await;
+    bot.conn.say("bob", "#test", "who's there?").await;
+    bot.conn.say("carol", "#test", "unrelated").await;
+    bot.conn.say("alice", "#test", "interrupting cow").await;
+    bot.conn.say("alice", "#elsewhere", "other channel").await;
+    assert_eq!(
+        bot.ask("dave", "u!convo 3 alice bob").await,
+        "Quote #1 added (3 messages)."
+    );
+    let convo = "<alice> knock knock <bob> who's there? <alice> interrupting cow (#1)";
+    assert_eq!(bot.ask("dave", "u!quote 1").await, convo);
+    // conversations can be found by any of their speakers
+    assert_eq!(bot.ask("dave", "u!quot bob").await, convo);
+    assert_eq!(
+        bot.ask("dave", "u!convo 2").await,
+        "Quote #2 added (2 messages)."
+    );
+    assert_eq!(
+        bot.ask("dave", "u!quote 2").await,
+        "<carol> unrelated <alice> interrupting cow (#2)"
+    );
+    // without a count, the last message of each named user is grabbed
+    bot.conn.say("alice", "#test", "moo").await;
+    assert_eq!(
+        bot.ask("dave", "u!convo alice bob").await,
+        "Quote #3 added (2 messages)."
+    );
+    assert_eq!(
+        bot.ask("dave", "u!quote 3").await,
+        "<bob> who's there? <alice> moo (#3)"
+    );
+}
+
+#[tokio::test]
+async fn conversation_grab_errors() {
+    let mut bot = TestBot::start().await;
+    assert_eq!(bot.ask("bob", "u!convo").await, "Invalid usage.");
+    assert_eq!(
+        bot.ask("bob", "u!convo 3").await,
+        "No previous messages to grab."
+    );
+    bot.conn.say("bob", "#test", "hi").await;
+    assert_eq!(
+        bot.ask("bob", "u!convo 3").await,
+        "You can't grab yourself."
+    );
+    assert_eq!(
+        bot.ask("bob", "u!convo 0 alice").await,
+        "So are you going to grab anything?"
+    );
+}
+
+#[tokio::test]
+async fn grab_matching_message() {
+    let mut bot = TestBot::start().await;
+    bot.conn.say("alice", "#test", "I like cats").await;
+    bot.conn.say("alice", "#test", "they are fluffy").await;
+    bot.conn.say("alice", "#test", "anyway").await;
+    assert_eq!(
+        bot.ask("bob", "u!grab alice \"LIKE CATS\"").await,
+        "Quote #1 added (1 messages)."
+    );
+    // a range of messages ending at the match
+    assert_eq!(
+        bot.ask("bob", "u!grab 2 alice /^any.+y$/").await,
+        "Quote #2 added (2 messages)."
+    );
+    assert_eq!(
+        bot.ask("bob", "u!quote 2").await,
+        "\"they are fluffy | anyway\" ~alice (#2)"
+    );
+    assert_eq!(
+        bot.ask("bob", "u!grab alice /FLUFFY/i").await,
+        "This was already quoted as #2."
+    );
+    assert_eq!(
+        bot.ask("bob", "u!grab alice \"dogs\"").await,
+        "No matching message to grab."
+    );
+    assert_eq!(
+        bot.ask("bob", "u!grab alice /(/").await,
+        "Invalid regular expression."
+    );
+}
+
+#[tokio::test]
+async fn quot_with_query() {
+    let db = TempDb::new();
+    db.seed_quotes(&[
+        ("alice", "I like cats"),
+        ("alice", "dogs are fine"),
+        ("bob", "cats are evil"),
+    ])
+    .await;
+    let mut bot = TestBot::start_with(BASE_CONFIG, db, |_| {}).await;
+    assert_eq!(
+        bot.ask("carol", "u!quot alice cats").await,
+        "\"I like cats\" ~alice (#1)"
+    );
+    assert_eq!(
+        bot.ask("carol", "u!quot bob dogs").await,
+        "No matching quotes found."
+    );
+    assert_eq!(
+        bot.ask("carol", "u!quot bob what's up?").await,
+        "No matching quotes found."
+    );
+}
+
+#[tokio::test]
+async fn quot_in_channel_with_details() {
+    let mut bot = TestBot::start().await;
+    bot.conn.say("alice", "#test", "hello").await;
+    bot.ask("bob", "u!grab alice").await;
+    let conn = Connection::open(&bot.db.path).unwrap();
+    conn.execute("update quotes set timestamp = 1700000000", [])
+        .unwrap();
+    assert_eq!(
+        bot.ask("carol", "u!quot -c #TEST -v").await,
+        "\"hello\" ~alice (#1), grabbed by bob on 2023-11-14"
+    );
+    assert_eq!(
+        bot.ask("carol", "u!quot -c #other").await,
+        "No matching quotes found."
+    );
+    assert_eq!(bot.ask("carol", "u!quot -x").await, "Invalid usage.");
+}
+
+#[tokio::test]
+async fn quote_stats() {
+    let mut bot = TestBot::start().await;
+    assert_eq!(
+        bot.ask("carol", "u!qstats").await,
+        "There are no quotes yet."
+    );
+    bot.conn.say("alice", "#test", "first").await;
+    bot.ask("carol", "u!grab alice").await;
+    bot.conn.say("alice", "#test", "second").await;
+    bot.ask("dave", "u!grab alice").await;
+    bot.conn.say("bob", "#test", "third").await;
+    bot.ask("carol", "u!convo 2 alice bob").await;
+    let conn = Connection::open(&bot.db.path).unwrap();
+    // 2023-11-14, 2023-11-15 and 2023-11-16
+    conn.execute(
+        "update quotes set timestamp = 1699920000 + (id - 1) * 86400",
+        [],
+    )
+    .unwrap();
+    assert_eq!(
+        bot.ask("carol", "u!qstats").await,
+        "3 quotes. Most quoted: alice (3), bob (1). Top grabbers: carol (2), dave (1)."
+    );
+    assert_eq!(
+        bot.ask("carol", "u!qstats Alice").await,
+        "Alice has 3 quotes and grabbed 0 quotes. First quoted on 2023-11-14, last quoted on 2023-11-16."
+    );
+    assert_eq!(
+        bot.ask("carol", "u!qstats erin").await,
+        "erin has 0 quotes and grabbed 0 quotes."
+    );
+}
@@ -0,0 +1,60 @@
+#![cfg(unix)]
+
+mod common;
+
+use common::{MockServer, TempDb};
+use std::{env, fs, process::Command};
+use tokio::sync::oneshot;
+use uberbot::{BotBuilder, UberConfig};
+
+fn config(port: u16, db: &TempDb, channels: &str, prefix: &str) -> String {
+    format!(
+        r#"
+[irc]
+host = "127.0.0.1"
+port = {port}
+tls = false
+username = "uberbot"
+channels = [{channels}]
+
+[bot]
+prefixes = ["{prefix}"]
+history_depth = 5
+db_path = {:?}
+"#,
+        db.path_str()
+    )
+}
+
+#[tokio::test]
+async fn sighup_reloads_config() {
+    let server = MockServer::bind().await;
+    let db = TempDb::new();
+    let path = env::temp_dir().join(format!("uberbot-reload-{}.toml", std::process::id()));
+    fs::write(&path, config(server.port(), &db, r##""#test""##, "u!")).unwrap();
+
+    let mut builder = BotBuilder::new(UberConfig::load(&path).unwrap());
+    builder.add_builtin_commands().await.unwrap();
+    builder.config_path(&path);
+    let (_shutdown, shutdown_rx) = oneshot::channel::<()>();
+    tokio::spawn(builder.run_until(async {
+        let _ = shutdown_rx.await;
+    }));
+    let mut conn = server.accept().await;
+    conn.expect_line("JOIN #test").await;
+
+    fs::write(&path, config(server.port(), &db, r##""#new""##, "u?")).unwrap();
+    let status = Command::new("kill")
+        .args(["-HUP", &std::process::id().to_string()])
+        .status()
+        .unwrap();
+    assert!(status.success());
+    conn.expect_line("JOIN #new").await;
+    conn.expect_line("PART #test").await;
+
+    // the old prefix no longer works
+    conn.say("alice", "#new", "u!nope").await;
+    conn.say("alice", "#new", "u?nope").await;
+    assert_eq!(conn.expect_reply("#new").await, "Unknown command.");
+    let _ = fs::remove_file(&path);
+}
@@ -0,0 +1,84 @@
+#![cfg(feature = "web")]
+mod common;
+
+use common::{TempDb, TestBot, BASE_CONFIG};
+use std::{net::TcpListener, time::Duration};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpStream,
+    time::sleep,
+};
+
+async fn start() -> (TestBot, u16) {
+    // find a free port for the web service
+    let port = TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+    let config = format!(
+        "{BASE_CONFIG}\n[web]\nlisten = \"127.0.0.1:{port}\"\n[web.webhooks]\ndevel = \"#test\"\n"
+    );
+    (
+        TestBot::start_with(&config, TempDb::new(), |_| {}).await,
+        port,
+    )
+}
+
+/// Sends a POST request and returns the status line of the response
+async fn post(port: u16, path: &str, content_type: &str, body: &str) -> String {
+    let mut stream = None;
+    for _ in 0..50 {
+        if let Ok(s) = TcpStream::connect(("127.0.0.1", port)).await {
+            stream = Some(s);
+            break;
+        }
+        sleep(Duration::from_millis(20)).await;
+    }
+    let mut stream = stream.expect("the web service didn't start");
+    let request = format!(
+        "POST {path} HTTP/1.1\r\nHost: localhost\r\nContent-Type: {content_type}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    );
+    stream.write_all(request.as_bytes()).await.unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).await.unwrap();
+    response.lines().next().unwrap_or_default().to_string()
+}
+
+#[tokio::test]
+async fn webhook_is_sent_to_channel() {
+    let (mut bot, port) = start().await;
+    let body = r#"{"username": "CI", "content": "build passed", "embeds": [{"title": "main", "url": "https://example.com"}]}"#;
+    assert_eq!(
+        post(port, "/devel", "application/json", body).await,
+        "HTTP/1.1 200 OK"
+    );
+    let mut lines = Vec::new();
+    for _ in 0..5 {
+        lines.push(bot.conn.expect_reply("#test").await);
+    }
+    assert_eq!(
+        lines,
+        [
+            "-- [Webhook: CI]",
+            "  build passed",
+            "-> main",
+            "   url: https://example.com",
+            "-- end of webhook"
+        ]
+    );
+}
+
+#[tokio::test]
+async fn unknown_webhook() {
+    let (_bot, port) = start().await;
+    assert_eq!(
+        post(port, "/nope", "application/json", "{}").await,
+        "HTTP/1.1 404 Not Found"
+    );
+    assert_eq!(
+        post(port, "/devel", "text/plain", "{}").await,
+        "HTTP/1.1 400 Bad Request"
+    );
+}